@@ -17,6 +17,13 @@ pub enum CommandInner {
     Commit(CCommit),
     CreateCommonBranch(CCreateCommonBranch),
     MergeBranch(CMergeBranch),
+    ResolveMerge(CResolveMerge),
+    Revert(CRevert),
+    CherryPick(CCherryPick),
+    Rebase(CRebase),
+    DeleteBranch(CDeleteBranch),
+    ArchiveBranch(CArchiveBranch),
+    CreateTag(CCreateTag),
 }
 
 #[derive(Debug, Deserialize)]
@@ -54,4 +61,60 @@ pub struct CMergeBranch {
     pub from: Branch,
     pub to: Branch,
     pub comment: String,
+    /// commit the net changes of `from` as a single non-merge commit
+    #[serde(default)]
+    pub squash: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CResolveMerge {
+    pub from: Branch,
+    pub to: Branch,
+    pub comment: String,
+    /// tip of `to` the conflicts were resolved against
+    pub prev: String,
+    /// tip of `from` the conflicts were resolved against
+    pub merge: String,
+    pub resolve: Vec<CRev>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CRevert {
+    pub comment: String,
+    pub branch: Branch,
+    pub prev: String,
+    /// the commit to undo
+    pub commit: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CCherryPick {
+    pub comment: String,
+    pub branch: Branch,
+    pub prev: String,
+    /// the commit to apply onto `branch`
+    pub commit: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CRebase {
+    pub branch: Branch,
+    pub onto: Branch,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CDeleteBranch {
+    pub branch: Branch,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CArchiveBranch {
+    pub branch: Branch,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CCreateTag {
+    pub name: String,
+    pub commit: String,
+    pub message: String,
 }
@@ -0,0 +1,113 @@
+use crate::{prelude::*, diff::{split_lines, diff_lines, LineOp}};
+
+// stored page: `DELTA_MAGIC`, then a msgpack `PageDelta`
+// 0xff never occurs in UTF-8, so full pages cannot be mistaken for deltas
+
+const DELTA_MAGIC: [u8; 4] = [0xff, b'L', b'B', b'D'];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "kind")]
+enum DeltaOp {
+    /// bytes `start..start + len` of the base
+    Copy { start: usize, len: usize },
+    Insert { text: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageDelta {
+    #[serde(with = "serde_bytes")]
+    pub base: Hash,
+    /// deltas to apply to get from a full page to this one, this one included
+    pub depth: u32,
+    ops: Vec<DeltaOp>,
+}
+
+impl PageDelta {
+    /// Line based, unchanged lines are copied from `base_page`.
+    pub fn new(base: Hash, depth: u32, base_page: &str, page: &str) -> PageDelta {
+        let (old, new) = (split_lines(base_page), split_lines(page));
+        let mut starts = Vec::with_capacity(old.len());
+        let mut start = 0;
+        for line in &old {
+            starts.push(start);
+            start += line.len();
+        }
+        let mut ops: Vec<DeltaOp> = Vec::new();
+        for op in diff_lines(&old, &new) {
+            match (op, ops.last_mut()) {
+                (LineOp::Equal { old: i, .. }, Some(DeltaOp::Copy { start, len })) if *start + *len == starts[i] => *len += old[i].len(),
+                (LineOp::Equal { old: i, .. }, _) => ops.push(DeltaOp::Copy { start: starts[i], len: old[i].len() }),
+                (LineOp::Insert { new: j }, Some(DeltaOp::Insert { text })) => text.push_str(new[j]),
+                (LineOp::Insert { new: j }, _) => ops.push(DeltaOp::Insert { text: new[j].to_owned() }),
+                (LineOp::Delete { .. }, _) => {},
+            }
+        }
+        PageDelta { base, depth, ops }
+    }
+
+    pub fn apply(&self, base_page: &str) -> anyhow::Result<String> {
+        let mut page = String::new();
+        for op in &self.ops {
+            match op {
+                DeltaOp::Copy { start, len } => page.push_str(base_page.get(*start..*start + *len)
+                    .ok_or_else(|| anyhow::anyhow!("delta copies {}..{} out of its base", start, start + len))?),
+                DeltaOp::Insert { text } => page.push_str(text),
+            }
+        }
+        Ok(page)
+    }
+
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let mut blob = DELTA_MAGIC.to_vec();
+        blob.extend(rmp_serde::to_vec_named(self)?);
+        Ok(blob)
+    }
+
+    /// `None` if `stored` is a full page.
+    pub fn decode(stored: &[u8]) -> anyhow::Result<Option<PageDelta>> {
+        match stored.strip_prefix(&DELTA_MAGIC) {
+            Some(raw) => Ok(Some(rmp_serde::from_slice(raw)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{prelude::*, model::RepoConfig, testutil::TestRepo};
+    use super::PageDelta;
+
+    fn repo_with_deltas(name: &str) -> TestRepo {
+        TestRepo::new(name, RepoConfig { page_delta_depth: 3, ..RepoConfig::default() })
+    }
+
+    fn long_page(edit: &str) -> String {
+        (0..200).map(|i| if i == 100 { format!("{}\n", edit) } else { format!("line {}\n", i) }).collect()
+    }
+
+    #[test]
+    fn resubmitted_packed_page() {
+        let repo = repo_with_deltas("resubmit");
+        let original = long_page("line 100");
+        let a = repo.add_page_object(original.clone()).unwrap();
+        let b = repo.add_page_object_against(long_page("vandalized"), Some(a)).unwrap();
+        assert!(repo.get_page_delta(b).unwrap().is_some());
+        repo.repack().unwrap();
+        // reverting, `a` must not become a delta against `b`
+        assert_eq!(repo.add_page_object_against(original.clone(), Some(b)).unwrap(), a);
+        assert_eq!(repo.get_page_object(a).unwrap(), original);
+        assert!(repo.fsck().unwrap().is_ok());
+    }
+
+    #[test]
+    fn delta_cycle_is_an_error() {
+        let repo = repo_with_deltas("cycle");
+        let (page_a, page_b) = (long_page("a"), long_page("b"));
+        let (a, b) = (hash_all(page_a.as_bytes()), hash_all(page_b.as_bytes()));
+        let pages = repo.dir.join("objects").join("page");
+        fs::write(pages.join(hash_to_hex(a).as_ref()), PageDelta::new(b, 2, &page_b, &page_a).encode().unwrap()).unwrap();
+        fs::write(pages.join(hash_to_hex(b).as_ref()), PageDelta::new(a, 1, &page_a, &page_b).encode().unwrap()).unwrap();
+        assert!(repo.get_page_object(a).is_err());
+        assert!(!repo.fsck().unwrap().is_ok());
+    }
+}
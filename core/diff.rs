@@ -0,0 +1,312 @@
+use crate::{prelude::*, model::*, repo::Repo};
+
+// region: state diff
+
+#[derive(Debug, Clone, Default)]
+pub struct StateMapDiff {
+    pub added: BTreeMap<String, Hash>,
+    pub removed: BTreeMap<String, Hash>,
+    /// path -> (old, new)
+    pub modified: BTreeMap<String, (Hash, Hash)>,
+}
+
+impl StateMapDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StateDiff {
+    pub data: StateMapDiff,
+    pub page: StateMapDiff,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.page.is_empty()
+    }
+}
+
+pub fn diff_state_map(old: &StateMap, new: &StateMap) -> StateMapDiff {
+    let mut diff = StateMapDiff::default();
+    for (path, old_hash) in old {
+        match new.get(path) {
+            None => { diff.removed.insert(path.clone(), *old_hash); },
+            Some(new_hash) if new_hash != old_hash => { diff.modified.insert(path.clone(), (*old_hash, *new_hash)); },
+            Some(_) => {},
+        }
+    }
+    for (path, new_hash) in new {
+        if !old.contains_key(path) {
+            diff.added.insert(path.clone(), *new_hash);
+        }
+    }
+    diff
+}
+
+pub fn diff_states(old: &State, new: &State) -> StateDiff {
+    StateDiff { data: diff_state_map(&old.data, &new.data), page: diff_state_map(&old.page, &new.page) }
+}
+
+// endregion
+
+// region: data object diff
+
+/// One operation of an RFC 6902 JSON Patch.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "op")]
+pub enum JsonPatchOp {
+    Add { path: String, value: Json },
+    Remove { path: String },
+    Replace { path: String, value: Json },
+}
+
+fn escape_pointer_token(token: &str) -> String {
+    // RFC 6901: '~' must be escaped first
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn diff_json_inner(old: &Json, new: &Json, pointer: &str, patch: &mut Vec<JsonPatchOp>) {
+    match (old, new) {
+        _ if old == new => {},
+        (Json::Object(old), Json::Object(new)) => {
+            for (key, old_value) in old {
+                let path = format!("{}/{}", pointer, escape_pointer_token(key));
+                match new.get(key) {
+                    Some(new_value) => diff_json_inner(old_value, new_value, &path, patch),
+                    None => patch.push(JsonPatchOp::Remove { path }),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    let path = format!("{}/{}", pointer, escape_pointer_token(key));
+                    patch.push(JsonPatchOp::Add { path, value: new_value.clone() });
+                }
+            }
+        },
+        (Json::Array(old), Json::Array(new)) => {
+            let common = old.len().min(new.len());
+            for (i, (old_value, new_value)) in old.iter().zip(new.iter()).enumerate() {
+                diff_json_inner(old_value, new_value, &format!("{}/{}", pointer, i), patch);
+            }
+            // removed from the back so that the remaining indexes stay valid
+            for i in (common..old.len()).rev() {
+                patch.push(JsonPatchOp::Remove { path: format!("{}/{}", pointer, i) });
+            }
+            for (i, new_value) in new.iter().enumerate().skip(common) {
+                patch.push(JsonPatchOp::Add { path: format!("{}/{}", pointer, i), value: new_value.clone() });
+            }
+        },
+        _ => patch.push(JsonPatchOp::Replace { path: pointer.to_owned(), value: new.clone() }),
+    }
+}
+
+/// Structural diff of two json values, applying the result to `old` in order gives `new`.
+pub fn diff_json(old: &Json, new: &Json) -> Vec<JsonPatchOp> {
+    let mut patch = Vec::new();
+    diff_json_inner(old, new, "", &mut patch);
+    patch
+}
+
+// endregion
+
+// region: page object diff
+
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineOp {
+    Equal { old: usize, new: usize },
+    Delete { old: usize },
+    Insert { new: usize },
+}
+
+/// Splits a page into lines, keeping the line terminators.
+pub fn split_lines(page: &str) -> Vec<&str> {
+    page.split_inclusive('\n').collect()
+}
+
+fn myers_prev_k(v: &[isize], offset: isize, k: isize, d: isize) -> isize {
+    let i = (k + offset) as usize;
+    if k == -d || (k != d && v[i - 1] < v[i + 1]) { k + 1 } else { k - 1 }
+}
+
+/// Shortest edit script from `old` to `new` (Myers' O(ND) algorithm).
+pub fn diff_lines<T: PartialEq>(old: &[T], new: &[T]) -> Vec<LineOp> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let offset = n + m;
+    let mut v = vec![0isize; (2 * offset + 2) as usize];
+    let mut trace = Vec::new();
+    'outer: for d in 0..=offset {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let prev_k = myers_prev_k(&v, offset, k, d);
+            let mut x = if prev_k == k + 1 { v[(prev_k + offset) as usize] } else { v[(prev_k + offset) as usize] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(k + offset) as usize] = x;
+            if x >= n && y >= m {
+                break 'outer;
+            }
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let prev_k = myers_prev_k(v, offset, x - y, d);
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            ops.push(LineOp::Equal { old: x as usize, new: y as usize });
+        }
+        if d > 0 {
+            if x == prev_x {
+                y -= 1;
+                ops.push(LineOp::Insert { new: y as usize });
+            } else {
+                x -= 1;
+                ops.push(LineOp::Delete { old: x as usize });
+            }
+        }
+    }
+    ops.reverse();
+    ops
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HunkLine {
+    Context(String),
+    Delete(String),
+    Insert(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    /// 0-based line index in the old page
+    pub old_start: usize,
+    pub old_len: usize,
+    /// 0-based line index in the new page
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+fn unified_range(start: usize, len: usize) -> String {
+    // an empty range is addressed by the line before it
+    let start = if len == 0 { start } else { start + 1 };
+    if len == 1 { format!("{}", start) } else { format!("{},{}", start, len) }
+}
+
+impl Hunk {
+    pub fn header(&self) -> String {
+        format!("@@ -{} +{} @@", unified_range(self.old_start, self.old_len), unified_range(self.new_start, self.new_len))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PageDiff {
+    pub hunks: Vec<Hunk>,
+}
+
+impl PageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    pub fn to_unified(&self, old_name: &str, new_name: &str) -> String {
+        let mut buf = String::new();
+        if self.is_empty() {
+            return buf;
+        }
+        buf.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+        for hunk in &self.hunks {
+            buf.push_str(&hunk.header());
+            buf.push('\n');
+            for line in &hunk.lines {
+                let (sign, content) = match line {
+                    HunkLine::Context(content) => (' ', content),
+                    HunkLine::Delete(content) => ('-', content),
+                    HunkLine::Insert(content) => ('+', content),
+                };
+                buf.push(sign);
+                buf.push_str(content);
+                if !content.ends_with('\n') {
+                    buf.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+        buf
+    }
+}
+
+pub fn diff_pages(old: &str, new: &str) -> PageDiff {
+    let (old, new) = (split_lines(old), split_lines(new));
+    let ops = diff_lines(&old, &new);
+
+    // group changes closer than two contexts into one hunk
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for (i, op) in ops.iter().enumerate() {
+        if matches!(op, LineOp::Equal { .. }) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some((_, end)) if i - *end <= 2 * DIFF_CONTEXT => *end = i + 1,
+            _ => ranges.push((i, i + 1)),
+        }
+    }
+
+    let mut hunks = Vec::new();
+    for (start, end) in ranges {
+        let (start, end) = (start.saturating_sub(DIFF_CONTEXT), (end + DIFF_CONTEXT).min(ops.len()));
+        let (old_start, new_start) = match ops[start] {
+            LineOp::Equal { old, new } => (old, new),
+            // a leading change is preceded by the lines consumed before it
+            LineOp::Delete { old } => (old, ops[..start].iter().filter(|op| !matches!(op, LineOp::Delete { .. })).count()),
+            LineOp::Insert { new } => (ops[..start].iter().filter(|op| !matches!(op, LineOp::Insert { .. })).count(), new),
+        };
+        let mut hunk = Hunk { old_start, old_len: 0, new_start, new_len: 0, lines: Vec::new() };
+        for op in &ops[start..end] {
+            hunk.lines.push(match *op {
+                LineOp::Equal { old: i, .. } => {
+                    hunk.old_len += 1;
+                    hunk.new_len += 1;
+                    HunkLine::Context(old[i].to_owned())
+                },
+                LineOp::Delete { old: i } => {
+                    hunk.old_len += 1;
+                    HunkLine::Delete(old[i].to_owned())
+                },
+                LineOp::Insert { new: i } => {
+                    hunk.new_len += 1;
+                    HunkLine::Insert(new[i].to_owned())
+                },
+            });
+        }
+        hunks.push(hunk);
+    }
+    PageDiff { hunks }
+}
+
+// endregion
+
+impl Repo {
+    pub fn diff_commits(&self, old: Hash, new: Hash) -> anyhow::Result<StateDiff> {
+        Ok(diff_states(&self.get_state(old)?, &self.get_state(new)?))
+    }
+
+    pub fn diff_data_objects(&self, old: Hash, new: Hash) -> anyhow::Result<Vec<JsonPatchOp>> {
+        Ok(diff_json(&self.get_data_object(old)?, &self.get_data_object(new)?))
+    }
+
+    pub fn diff_page_objects(&self, old: Hash, new: Hash) -> anyhow::Result<PageDiff> {
+        Ok(diff_pages(&self.get_page_object(old)?, &self.get_page_object(new)?))
+    }
+}
@@ -1,44 +1,165 @@
-use crate::{prelude::*, model::*, command::*, repo::Repo};
+use crate::{prelude::*, model::*, command::*, repo::{Repo, check_ref_author}, merge::MergeConflicts};
 
 impl Repo {
+    /// Pages are stored against the revision at the same path in `prev`, if deltas are enabled.
+    fn add_rev(&self, crev: Vec<CRev>, prev: Hash) -> anyhow::Result<Vec<Rev>> {
+        let bases = if self.config().page_delta_depth > 0 { self.get_state(prev)?.page } else { StateMap::new() };
+        let mut rev = Vec::new();
+        for CRev { inner, object_kind, path } in crev {
+            let inner = match inner {
+                CRevInner::Update { content } => {
+                    let hash = match object_kind {
+                        ObjectKind::Data => self.add_data_object(content)?,
+                        ObjectKind::Page => self.add_page_object_against(json_to_string(content)?, bases.get(&path).copied())?,
+                    };
+                    RevInner::Update { hash }
+                },
+                CRevInner::Remove => RevInner::Remove,
+            };
+            rev.push(Rev { inner, object_kind, path });
+        }
+        Ok(rev)
+    }
+
+    fn squash_comment(&self, comment: String, tip: Hash, upstream: Hash) -> anyhow::Result<String> {
+        let mut comment = comment;
+        comment.push('\n');
+        for (_, commit) in self.branch_commits(tip, upstream)? {
+            comment.push_str(&format!("\n* {}", commit.comment.lines().next().unwrap_or_default()));
+        }
+        Ok(comment)
+    }
+
+    fn check_removable(&self, branch: &Branch) -> anyhow::Result<()> {
+        if matches!(branch, Branch::Main) || branch == &self.config().online_branch {
+            return Err(anyhow::anyhow!("cannot remove branch {}", branch.to_string()));
+        }
+        Ok(())
+    }
+
     pub fn exec(&self, cmd: Command) -> anyhow::Result<()> {
         let Command { ts, author, inner } = cmd;
+        // before anything is written for the command
+        check_ref_author(&author)?;
         match inner {
-            CommandInner::Commit(CCommit { comment, branch, prev, rev: crev }) => {
+            CommandInner::Commit(CCommit { comment, branch, prev, rev }) => {
                 // TODO prem check
                 let prev = hex_to_hash(prev)?;
-                assert_eq!(self.get_ref(&branch)?, prev);
-                let mut rev = Vec::new();
-                for CRev { inner, object_kind, path } in crev {
-                    let inner = match inner {
-                        CRevInner::Update { content } => {
-                            let hash = match object_kind {
-                                ObjectKind::Data => self.add_data_object(content)?,
-                                ObjectKind::Page => self.add_page_object(json_to_string(content)?)?,
-                            };
-                            RevInner::Update { hash }
-                        },
-                        CRevInner::Remove => RevInner::Remove,
-                    };
-                    rev.push(Rev { inner, object_kind, path });
-                }
-                self.commit(Commit { prev, ts, author, comment, merge: None, rev }, vec![&branch])?;
+                self.check_ref(&branch, prev)?;
+                let rev = self.add_rev(rev, prev)?;
+                self.commit(Commit { prev, ts, author, comment, merge: None, merge_hash: None, rev }, vec![(&branch, prev)], RefOp::Commit)?;
             },
             CommandInner::CreateCommonBranch(CCreateCommonBranch { prev }) => {
-                self.create_ref(&Branch::Common(CommonBranch { ts, author }), hex_to_hash(prev)?)?;
+                let entry = RefLogEntry { hash: hex_to_hash(prev)?, ts, author: author.clone(), op: RefOp::Create };
+                self.create_ref(&Branch::Common(CommonBranch { ts, author }), entry)?;
             },
-            CommandInner::MergeBranch(CMergeBranch { from, to, comment }) => {
+            CommandInner::MergeBranch(CMergeBranch { from, to, comment, squash }) => {
                 // TODO prem check
-                let prev = self.get_ref(&from)?;
-                let commit = if self.get_ref(&to)? == self.get_root_ref(&from)? {
+                let ours = self.get_ref(&to)?;
+                let theirs = self.get_ref(&from)?;
+                let base = self.merge_base(ours, theirs)?;
+                if base == theirs {
+                    return Err(anyhow::anyhow!("{} is already merged into {}", from.to_string(), to.to_string()));
+                }
+                let commit = if squash {
+                    let outcome = self.merge_commits(base, ours, theirs)?;
+                    if !outcome.conflicts.is_empty() {
+                        return Err(MergeConflicts(outcome.conflicts).into());
+                    }
+                    let comment = self.squash_comment(comment, theirs, ours)?;
+                    Commit { prev: ours, ts, author, comment, merge: None, merge_hash: None, rev: outcome.rev }
+                } else if ours == base {
                     // fast-forward
-                    Commit { prev, ts, author, comment, merge: Some(from.clone()), rev: Vec::new() }
+                    Commit { prev: theirs, ts, author, comment, merge: Some(from), merge_hash: Some(theirs), rev: Vec::new() }
                 } else {
                     // 3-way
-                    unimplemented!()
+                    let outcome = self.merge_commits(base, ours, theirs)?;
+                    if !outcome.conflicts.is_empty() {
+                        return Err(MergeConflicts(outcome.conflicts).into());
+                    }
+                    Commit { prev: ours, ts, author, comment, merge: Some(from), merge_hash: Some(theirs), rev: outcome.rev }
                 };
-                self.commit(commit, vec![&to])?;
-            }
+                self.commit(commit, vec![(&to, ours)], RefOp::Merge)?;
+            },
+            CommandInner::ResolveMerge(CResolveMerge { from, to, comment, prev, merge, resolve }) => {
+                // TODO prem check
+                let ours = hex_to_hash(prev)?;
+                let theirs = hex_to_hash(merge)?;
+                self.check_ref(&to, ours)?;
+                self.check_ref(&from, theirs)?;
+                let outcome = self.merge_commits(self.merge_base(ours, theirs)?, ours, theirs)?;
+                let mut unresolved = outcome.conflicts;
+                for CRev { object_kind, path, .. } in &resolve {
+                    let pos = unresolved.iter()
+                        .position(|conflict| conflict.object_kind == *object_kind && &conflict.path == path)
+                        .ok_or_else(|| anyhow::anyhow!("no conflict to resolve at {}:{}", object_kind.to_sign(), path))?;
+                    unresolved.swap_remove(pos);
+                }
+                if !unresolved.is_empty() {
+                    return Err(MergeConflicts(unresolved).into());
+                }
+                let mut rev = outcome.rev;
+                rev.extend(self.add_rev(resolve, ours)?);
+                self.commit(Commit { prev: ours, ts, author, comment, merge: Some(from), merge_hash: Some(theirs), rev }, vec![(&to, ours)], RefOp::Merge)?;
+            },
+            CommandInner::Revert(CRevert { comment, branch, prev, commit }) => {
+                // TODO prem check
+                let prev = hex_to_hash(prev)?;
+                self.check_ref(&branch, prev)?;
+                let reverted = self.get_commit(hex_to_hash(commit)?)?;
+                if reverted.rev.is_empty() {
+                    return Err(anyhow::anyhow!("nothing to revert"));
+                }
+                let parent = self.get_state(reverted.prev)?;
+                let mut rev: Vec<Rev> = Vec::new();
+                for Rev { object_kind, path, .. } in reverted.rev {
+                    if !rev.iter().any(|rev| rev.object_kind == object_kind && rev.path == path) {
+                        let inner = RevInner::from_hash(parent.map(object_kind).get(&path).copied());
+                        rev.push(Rev { inner, object_kind, path });
+                    }
+                }
+                self.commit(Commit { prev, ts, author, comment, merge: None, merge_hash: None, rev }, vec![(&branch, prev)], RefOp::Revert)?;
+            },
+            CommandInner::CherryPick(CCherryPick { comment, branch, prev, commit }) => {
+                // TODO prem check
+                let prev = hex_to_hash(prev)?;
+                self.check_ref(&branch, prev)?;
+                let picked = hex_to_hash(commit)?;
+                // the picked commit's own changes are those against its parent
+                let outcome = self.merge_commits(self.get_commit(picked)?.prev, prev, picked)?;
+                if !outcome.conflicts.is_empty() {
+                    return Err(MergeConflicts(outcome.conflicts).into());
+                }
+                if outcome.rev.is_empty() {
+                    return Err(anyhow::anyhow!("nothing to cherry-pick"));
+                }
+                self.commit(Commit { prev, ts, author, comment, merge: None, merge_hash: None, rev: outcome.rev }, vec![(&branch, prev)], RefOp::CherryPick)?;
+            },
+            CommandInner::Rebase(CRebase { branch, onto }) => {
+                // TODO prem check
+                if branch == self.config().online_branch {
+                    return Err(anyhow::anyhow!("cannot rebase the online branch"));
+                }
+                let tip = self.get_ref(&branch)?;
+                let new_tip = self.rebase_commits(tip, self.get_ref(&onto)?)?;
+                if new_tip != tip {
+                    self.update_ref(&branch, tip, RefLogEntry { hash: new_tip, ts, author, op: RefOp::Rebase })?;
+                }
+            },
+            CommandInner::DeleteBranch(CDeleteBranch { branch }) => {
+                // TODO prem check
+                self.check_removable(&branch)?;
+                self.delete_ref(&branch)?;
+            },
+            CommandInner::ArchiveBranch(CArchiveBranch { branch }) => {
+                // TODO prem check
+                self.check_removable(&branch)?;
+                self.archive_ref(&branch)?;
+            },
+            CommandInner::CreateTag(CCreateTag { name, commit, message }) => {
+                // TODO prem check
+                self.create_tag(&name, Tag { hash: hex_to_hash(commit)?, ts, author, message })?;
+            },
         }
         Ok(())
     }
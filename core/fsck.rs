@@ -0,0 +1,113 @@
+use crate::{prelude::*, model::*, repo::{Repo, BlobKind}};
+
+#[derive(Debug, Clone)]
+pub enum FsckProblem {
+    /// the blob could not be read or decoded
+    Unreadable { kind: BlobKind, hash: Hash, error: String },
+    /// the blob content hashes to `actual` instead of its name
+    HashMismatch { kind: BlobKind, hash: Hash, actual: Hash },
+    MissingObject { commit: Hash, object_kind: ObjectKind, path: String, hash: Hash },
+    MissingParent { commit: Hash, parent: Hash },
+    MissingState { commit: Hash },
+    /// the stored state differs from the commit replayed on its parent state
+    StateMismatch { commit: Hash },
+}
+
+impl std::fmt::Display for FsckProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FsckProblem::Unreadable { kind, hash, error } =>
+                write!(f, "unreadable {} {}: {}", kind.to_sign(), hash_to_hex(*hash), error),
+            FsckProblem::HashMismatch { kind, hash, actual } =>
+                write!(f, "hash mismatch {} {}: content hashes to {}", kind.to_sign(), hash_to_hex(*hash), hash_to_hex(*actual)),
+            FsckProblem::MissingObject { commit, object_kind, path, hash } =>
+                write!(f, "commit {} references missing {} object {} at {}", hash_to_hex(*commit), object_kind.to_sign(), hash_to_hex(*hash), path),
+            FsckProblem::MissingParent { commit, parent } =>
+                write!(f, "commit {} references missing parent {}", hash_to_hex(*commit), hash_to_hex(*parent)),
+            FsckProblem::MissingState { commit } =>
+                write!(f, "commit {} has no state", hash_to_hex(*commit)),
+            FsckProblem::StateMismatch { commit } =>
+                write!(f, "state of commit {} does not match its replay", hash_to_hex(*commit)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FsckReport {
+    /// objects and commits re-hashed
+    pub checked: usize,
+    pub problems: Vec<FsckProblem>,
+}
+
+impl FsckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl Repo {
+    /// Reads every stored blob and reports all inconsistencies found instead of stopping at the first.
+    pub fn fsck(&self) -> anyhow::Result<FsckReport> {
+        let mut report = FsckReport::default();
+        let mut commits = Vec::new();
+        for kind in [BlobKind::Data, BlobKind::Page, BlobKind::Commit] {
+            for hash in self.list_blobs(kind)? {
+                report.checked += 1;
+                let blob = match self.get_blob(kind, hash) {
+                    Ok(blob) => blob,
+                    Err(err) => {
+                        report.problems.push(FsckProblem::Unreadable { kind, hash, error: err.to_string() });
+                        continue;
+                    },
+                };
+                let actual = hash_all(&blob);
+                if actual != hash {
+                    report.problems.push(FsckProblem::HashMismatch { kind, hash, actual });
+                } else if kind == BlobKind::Commit {
+                    match rmp_serde::from_slice::<Commit>(&blob) {
+                        Ok(commit) => commits.push((hash, commit)),
+                        Err(err) => report.problems.push(FsckProblem::Unreadable { kind, hash, error: err.to_string() }),
+                    }
+                }
+            }
+        }
+        for (hash, commit) in commits {
+            self.fsck_commit(hash, commit, &mut report.problems)?;
+        }
+        Ok(report)
+    }
+
+    fn fsck_commit(&self, hash: Hash, commit: Commit, problems: &mut Vec<FsckProblem>) -> anyhow::Result<()> {
+        for Rev { inner, object_kind, path } in &commit.rev {
+            if let RevInner::Update { hash: object } = inner {
+                if !self.has_blob((*object_kind).into(), *object)? {
+                    problems.push(FsckProblem::MissingObject { commit: hash, object_kind: *object_kind, path: path.clone(), hash: *object });
+                }
+            }
+        }
+        for parent in commit.parents() {
+            if parent != EMPTY_HASH && !self.has_blob(BlobKind::Commit, parent)? {
+                problems.push(FsckProblem::MissingParent { commit: hash, parent });
+            }
+        }
+        if !self.has_blob(BlobKind::State, hash)? {
+            problems.push(FsckProblem::MissingState { commit: hash });
+            return Ok(());
+        }
+        let state = match self.get_state(hash) {
+            Ok(state) => state,
+            Err(err) => {
+                problems.push(FsckProblem::Unreadable { kind: BlobKind::State, hash, error: err.to_string() });
+                return Ok(());
+            },
+        };
+        // a broken parent state is reported with the parent itself
+        if let Ok(mut expected) = self.get_state(commit.prev) {
+            expected.update(commit.rev);
+            if expected != state {
+                problems.push(FsckProblem::StateMismatch { commit: hash });
+            }
+        }
+        Ok(())
+    }
+}
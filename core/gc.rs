@@ -0,0 +1,129 @@
+use std::time::{Duration, SystemTime};
+use crate::{prelude::*, model::*, repo::{Repo, BlobKind}};
+
+const GC_DEFAULT_GRACE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct GcOptions {
+    /// only report what would be removed
+    pub dry_run: bool,
+    /// move unreachable blobs to `quarantine/` instead of deleting them
+    pub quarantine: bool,
+    /// blobs modified more recently are kept, they may belong to a commit in progress
+    pub grace: Duration,
+}
+
+impl Default for GcOptions {
+    fn default() -> GcOptions {
+        GcOptions { dry_run: false, quarantine: true, grace: GC_DEFAULT_GRACE }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    /// reachable commits
+    pub reachable: usize,
+    /// unreachable blobs kept for being within the grace period
+    pub recent: usize,
+    /// blobs removed, or that would be on a dry run
+    pub removed: Vec<(BlobKind, Hash)>,
+}
+
+/// Everything that must survive a gc, states are kept by their commit's hash.
+#[derive(Default)]
+struct Reachable {
+    data: HashSet<Hash>,
+    page: HashSet<Hash>,
+    commits: HashSet<Hash>,
+}
+
+impl Reachable {
+    fn contains(&self, kind: BlobKind, hash: Hash) -> bool {
+        match kind {
+            BlobKind::Data => self.data.contains(&hash),
+            BlobKind::Page => self.page.contains(&hash),
+            BlobKind::Commit => self.commits.contains(&hash),
+            BlobKind::State => hash == EMPTY_HASH || self.commits.contains(&hash),
+        }
+    }
+}
+
+impl Repo {
+    /// Hashes every live or archived ref has ever pointed at, and tagged commits.
+    fn gc_roots(&self) -> anyhow::Result<Vec<Hash>> {
+        let mut roots = Vec::new();
+        for BranchInfo { branch, .. } in self.list_branches()? {
+            roots.extend(self.get_all_ref(&branch)?);
+        }
+        for branch in self.list_archived_branches()? {
+            roots.extend(self.get_all_archived_ref(&branch)?);
+        }
+        for (_, tag) in self.list_tags()? {
+            roots.push(tag.hash);
+        }
+        Ok(roots)
+    }
+
+    fn gc_mark(&self) -> anyhow::Result<Reachable> {
+        let mut reachable = Reachable::default();
+        let mut stack = self.gc_roots()?;
+        while let Some(hash) = stack.pop() {
+            if hash == EMPTY_HASH || !reachable.commits.insert(hash) {
+                continue;
+            }
+            // a missing commit or state fails the gc rather than letting it sweep what they refer to
+            let commit = self.get_commit(hash)?;
+            let state = self.get_state(hash)?;
+            reachable.data.extend(state.data.into_values());
+            reachable.page.extend(state.page.into_values());
+            // a path updated twice in one commit keeps only the last hash in the state
+            for Rev { inner, object_kind, .. } in &commit.rev {
+                if let RevInner::Update { hash } = inner {
+                    match object_kind {
+                        ObjectKind::Data => reachable.data.insert(*hash),
+                        ObjectKind::Page => reachable.page.insert(*hash),
+                    };
+                }
+            }
+            stack.extend(commit.parents());
+        }
+        Ok(reachable)
+    }
+
+    /// Removes data/page objects, commits and states not reachable from any ref, reflog entry or tag.
+    pub fn gc(&self, options: &GcOptions) -> anyhow::Result<GcReport> {
+        let reachable = self.gc_mark()?;
+        let mut report = GcReport { reachable: reachable.commits.len(), ..GcReport::default() };
+        let now = SystemTime::now();
+        for kind in BlobKind::ALL {
+            for hash in self.list_blobs(kind)? {
+                if reachable.contains(kind, hash) {
+                    continue;
+                }
+                let age = now.duration_since(self.get_blob_modified(kind, hash)?).unwrap_or_default();
+                if age < options.grace {
+                    report.recent += 1;
+                    continue;
+                }
+                report.removed.push((kind, hash));
+            }
+        }
+        // a page stored as a delta keeps its base, even an unreachable one
+        let mut removed: HashSet<(BlobKind, Hash)> = report.removed.iter().copied().collect();
+        let mut kept: Vec<Hash> = self.list_blobs(BlobKind::Page)?.into_iter()
+            .filter(|hash| !removed.contains(&(BlobKind::Page, *hash)))
+            .collect();
+        while let Some(hash) = kept.pop() {
+            if let Some(delta) = self.get_page_delta(hash)? {
+                if removed.remove(&(BlobKind::Page, delta.base)) {
+                    kept.push(delta.base);
+                }
+            }
+        }
+        report.removed.retain(|blob| removed.contains(blob));
+        if !options.dry_run {
+            self.fs_remove_blobs(&report.removed, options.quarantine)?;
+        }
+        Ok(report)
+    }
+}
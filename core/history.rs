@@ -0,0 +1,219 @@
+use std::collections::BinaryHeap;
+use crate::{prelude::*, model::*, repo::Repo, diff::{split_lines, diff_lines, LineOp}};
+
+// region: log
+
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub author: Option<String>,
+    /// inclusive, compared with `Commit.ts`
+    pub since: Option<u64>,
+    /// inclusive, compared with `Commit.ts`
+    pub until: Option<u64>,
+    /// `Some(true)` for merge commits only, `Some(false)` to skip them
+    pub merge: Option<bool>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, commit: &Commit) -> bool {
+        !matches!(&self.author, Some(author) if &commit.author != author)
+            && !matches!(self.since, Some(since) if commit.ts < since)
+            && !matches!(self.until, Some(until) if commit.ts > until)
+            && !matches!(self.merge, Some(merge) if commit.merge.is_some() != merge)
+    }
+}
+
+/// Walks the commit graph backwards through both parents, newest `ts` first.
+pub struct Log<'a> {
+    repo: &'a Repo,
+    filter: LogFilter,
+    queue: BinaryHeap<(u64, Hash)>,
+    pending: HashMap<Hash, Commit>,
+    seen: HashSet<Hash>,
+}
+
+impl<'a> Log<'a> {
+    fn push(&mut self, hash: Hash) -> anyhow::Result<()> {
+        if hash != EMPTY_HASH && self.seen.insert(hash) {
+            let commit = self.repo.get_commit(hash)?;
+            self.queue.push((commit.ts, hash));
+            self.pending.insert(hash, commit);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for Log<'a> {
+    type Item = anyhow::Result<(Hash, Commit)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((_, hash)) = self.queue.pop() {
+            let commit = self.pending.remove(&hash).unwrap();
+            for parent in commit.parents() {
+                if let Err(err) = self.push(parent) {
+                    return Some(Err(err));
+                }
+            }
+            if self.filter.matches(&commit) {
+                return Some(Ok((hash, commit)));
+            }
+        }
+        None
+    }
+}
+
+// endregion
+
+// region: path history
+
+#[derive(Debug, Clone)]
+pub struct PathRev {
+    pub hash: Hash,
+    pub commit: Commit,
+    /// what the commit left at the path
+    pub inner: RevInner,
+}
+
+pub struct PathHistory<'a> {
+    log: Log<'a>,
+    object_kind: ObjectKind,
+    path: String,
+}
+
+impl<'a> Iterator for PathHistory<'a> {
+    type Item = anyhow::Result<PathRev>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for entry in self.log.by_ref() {
+            let (hash, commit) = match entry {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(err)),
+            };
+            // the last rev on a path is the one that ends up in the state
+            let inner = commit.rev.iter().rev()
+                .find(|rev| rev.object_kind == self.object_kind && rev.path == self.path)
+                .map(|rev| rev.inner.clone());
+            if let Some(inner) = inner {
+                return Some(Ok(PathRev { hash, commit, inner }));
+            }
+        }
+        None
+    }
+}
+
+// endregion
+
+// region: blame
+
+#[derive(Debug, Clone)]
+pub struct BlameLine {
+    /// without the line terminator
+    pub line: String,
+    /// the commit that introduced the line
+    pub hash: Hash,
+    pub author: String,
+    pub ts: u64,
+}
+
+// endregion
+
+impl Repo {
+    pub fn log(&self, start: Hash, filter: LogFilter) -> anyhow::Result<Log<'_>> {
+        let mut log = Log { repo: self, filter, queue: BinaryHeap::new(), pending: HashMap::new(), seen: HashSet::new() };
+        log.push(start)?;
+        Ok(log)
+    }
+
+    pub fn log_branch(&self, branch: &Branch, filter: LogFilter) -> anyhow::Result<Log<'_>> {
+        self.log(self.get_ref(branch)?, filter)
+    }
+
+    pub fn path_history(&self, start: Hash, object_kind: ObjectKind, path: &str, filter: LogFilter) -> anyhow::Result<PathHistory<'_>> {
+        Ok(PathHistory { log: self.log(start, filter)?, object_kind, path: path.to_owned() })
+    }
+
+    /// Attributes every line of the page at `path` in `start` to the commit that introduced it.
+    pub fn blame(&self, start: Hash, path: &str) -> anyhow::Result<Vec<BlameLine>> {
+        let mut tracked = *self.get_state(start)?.page.get(path)
+            .ok_or_else(|| anyhow::anyhow!("page {} not found", path))?;
+        let content = self.get_page_object(tracked)?;
+        let lines = split_lines(&content);
+        // index into `blamed` for every line
+        let mut blame: Vec<Option<usize>> = vec![None; lines.len()];
+        // index of each not yet blamed line in the tracked revision
+        let mut position: Vec<Option<usize>> = (0..lines.len()).map(Some).collect();
+        let mut tracked_content = content.clone();
+        let mut blamed = Vec::new();
+
+        for entry in self.path_history(start, ObjectKind::Page, path, LogFilter::default())? {
+            let PathRev { hash, commit, inner } = entry?;
+            if !matches!(inner, RevInner::Update { hash } if hash == tracked) {
+                // a revision on another line of history
+                continue;
+            }
+            if let Some(merged) = commit.merge_hash {
+                // taken as is from the merged branch, blame its own commits instead
+                if self.get_state(merged)?.page.get(path) == Some(&tracked) {
+                    continue;
+                }
+            }
+            let before = self.get_state(commit.prev)?.page.get(path).copied();
+            let before_content = match before {
+                Some(hash) => self.get_page_object(hash)?,
+                None => String::new(),
+            };
+            let tracked_lines = split_lines(&tracked_content);
+            let mut old_of_new = vec![None; tracked_lines.len()];
+            for op in diff_lines(&split_lines(&before_content), &tracked_lines) {
+                if let LineOp::Equal { old, new } = op {
+                    old_of_new[new] = Some(old);
+                }
+            }
+            blamed.push((hash, commit));
+            for (pos, blame) in position.iter_mut().zip(blame.iter_mut()) {
+                if let Some(i) = *pos {
+                    *pos = old_of_new[i];
+                    if pos.is_none() {
+                        *blame = Some(blamed.len() - 1);
+                    }
+                }
+            }
+            match before {
+                Some(hash) if position.iter().any(Option::is_some) => {
+                    tracked = hash;
+                    tracked_content = before_content;
+                },
+                _ => break,
+            }
+        }
+
+        lines.into_iter().zip(blame).map(|(line, blame)| {
+            // lines that survived the whole walk belong to the oldest revision found
+            let (hash, Commit { author, ts, .. }) = blame.or_else(|| blamed.len().checked_sub(1))
+                .map(|i| &blamed[i])
+                .ok_or_else(|| anyhow::anyhow!("no history found for page {}", path))?;
+            Ok(BlameLine { line: line.trim_end_matches('\n').to_owned(), hash: *hash, author: author.clone(), ts: *ts })
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{model::*, testutil::TestRepo};
+
+    #[test]
+    fn blame_through_merge() {
+        let repo = TestRepo::new("blame-merge", RepoConfig::default());
+        let root = repo.commit_pages(&Main, 1, &[("p", "one\ntwo\nthree\n")]);
+        let branch = repo.branch_at(2, root);
+        let theirs = repo.commit_pages(&branch, 3, &[("p", "one\n2\nthree\n")]);
+        let ours = repo.commit_pages(&Main, 4, &[("q", "q\n")]);
+        repo.merge(5, &branch, &Main).unwrap();
+        let merge = repo.get_ref(&Main).unwrap();
+        assert_eq!(repo.get_commit(merge).unwrap().parents(), vec![ours, theirs]);
+        let blame = repo.blame(merge, "p").unwrap();
+        let lines: Vec<_> = blame.iter().map(|line| (line.line.as_str(), line.hash)).collect();
+        // the merge only takes the page as it was on the branch
+        assert_eq!(lines, vec![("one", root), ("2", theirs), ("three", root)]);
+    }
+}
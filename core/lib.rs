@@ -8,6 +8,16 @@ pub mod model;
 pub mod schema;
 
 pub mod repo;
+pub mod pack;
+pub mod delta;
+pub mod merge;
+pub mod diff;
+pub mod history;
+pub mod fsck;
+pub mod gc;
 
 pub mod command;
 pub mod executor;
+
+#[cfg(test)]
+mod testutil;
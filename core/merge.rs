@@ -0,0 +1,276 @@
+use std::fmt;
+use crate::{prelude::*, model::*, repo::Repo};
+
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub object_kind: ObjectKind,
+    pub path: String,
+    pub base: Option<Hash>,
+    pub ours: Option<Hash>,
+    pub theirs: Option<Hash>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeOutcome {
+    /// changes from `theirs` that apply cleanly on `ours`
+    pub rev: Vec<Rev>,
+    pub conflicts: Vec<Conflict>,
+}
+
+#[derive(Debug)]
+pub struct MergeConflicts(pub Vec<Conflict>);
+
+impl fmt::Display for MergeConflicts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "merge conflicts at")?;
+        for Conflict { object_kind, path, .. } in &self.0 {
+            write!(f, " {}:{}", object_kind.to_sign(), path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MergeConflicts {}
+
+fn merge_map(
+    object_kind: ObjectKind,
+    base: &StateMap,
+    ours: &StateMap,
+    theirs: &StateMap,
+    outcome: &mut MergeOutcome,
+) {
+    // sorted to keep the generated rev (and so the commit hash) deterministic
+    let paths: BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+    for path in paths {
+        let (base, ours, theirs) = (base.get(path).copied(), ours.get(path).copied(), theirs.get(path).copied());
+        if ours == theirs || base == theirs {
+            continue;
+        }
+        if base != ours {
+            outcome.conflicts.push(Conflict { object_kind, path: path.clone(), base, ours, theirs });
+            continue;
+        }
+        outcome.rev.push(Rev { inner: RevInner::from_hash(theirs), object_kind, path: path.clone() });
+    }
+}
+
+/// Computes the rev that brings the changes made from `base` to `theirs` onto `ours`.
+pub fn merge_states(base: &State, ours: &State, theirs: &State) -> MergeOutcome {
+    let mut outcome = MergeOutcome { rev: Vec::new(), conflicts: Vec::new() };
+    merge_map(ObjectKind::Data, &base.data, &ours.data, &theirs.data, &mut outcome);
+    merge_map(ObjectKind::Page, &base.page, &ours.page, &theirs.page, &mut outcome);
+    outcome
+}
+
+impl Repo {
+    /// All commits reachable from `hash` through both parents, including itself.
+    pub fn ancestors(&self, hash: Hash) -> anyhow::Result<HashSet<Hash>> {
+        let mut seen = HashSet::new();
+        let mut queue = vec![hash];
+        while let Some(hash) = queue.pop() {
+            if seen.insert(hash) && hash != EMPTY_HASH {
+                queue.extend(self.get_commit(hash)?.parents());
+            }
+        }
+        Ok(seen)
+    }
+
+    pub fn is_ancestor(&self, ancestor: Hash, hash: Hash) -> anyhow::Result<bool> {
+        Ok(self.ancestors(hash)?.contains(&ancestor))
+    }
+
+    /// Common ancestors of `a` and `b` that are not ancestors of another common ancestor.
+    pub fn merge_bases(&self, a: Hash, b: Hash) -> anyhow::Result<Vec<Hash>> {
+        let ancestors_a = self.ancestors(a)?;
+        let mut candidates = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = vec![b];
+        while let Some(hash) = queue.pop() {
+            if !seen.insert(hash) {
+                continue;
+            }
+            if ancestors_a.contains(&hash) {
+                candidates.push(hash);
+            } else if hash != EMPTY_HASH {
+                queue.extend(self.get_commit(hash)?.parents());
+            }
+        }
+        let mut redundant = HashSet::new();
+        for candidate in &candidates {
+            if redundant.contains(candidate) {
+                continue;
+            }
+            let ancestors = self.ancestors(*candidate)?;
+            for other in &candidates {
+                if other != candidate && ancestors.contains(other) {
+                    redundant.insert(*other);
+                }
+            }
+        }
+        Ok(candidates.into_iter().filter(|candidate| !redundant.contains(candidate)).collect())
+    }
+
+    /// Best common ancestor of `a` and `b`, every history shares at least `EMPTY_HASH`.
+    pub fn merge_base(&self, a: Hash, b: Hash) -> anyhow::Result<Hash> {
+        // more than one base only happens after criss-cross merges, take the latest one
+        let mut best = (0, EMPTY_HASH);
+        for hash in self.merge_bases(a, b)? {
+            if hash != EMPTY_HASH {
+                let ts = self.get_commit(hash)?.ts;
+                if best.1 == EMPTY_HASH || ts > best.0 {
+                    best = (ts, hash);
+                }
+            }
+        }
+        Ok(best.1)
+    }
+
+    pub fn merge_commits(&self, base: Hash, ours: Hash, theirs: Hash) -> anyhow::Result<MergeOutcome> {
+        Ok(merge_states(&self.get_state(base)?, &self.get_state(ours)?, &self.get_state(theirs)?))
+    }
+
+    pub fn try_merge(&self, from: &Branch, to: &Branch) -> anyhow::Result<MergeOutcome> {
+        let (ours, theirs) = (self.get_ref(to)?, self.get_ref(from)?);
+        self.merge_commits(self.merge_base(ours, theirs)?, ours, theirs)
+    }
+
+    /// First-parent commits of `tip` that `upstream` lacks, oldest first.
+    pub fn branch_commits(&self, tip: Hash, upstream: Hash) -> anyhow::Result<Vec<(Hash, Commit)>> {
+        let upstream = self.ancestors(upstream)?;
+        let mut commits = Vec::new();
+        let mut hash = tip;
+        // terminates as every history ends in EMPTY_HASH
+        while !upstream.contains(&hash) {
+            let commit = self.get_commit(hash)?;
+            let prev = commit.prev;
+            commits.push((hash, commit));
+            hash = prev;
+        }
+        commits.reverse();
+        Ok(commits)
+    }
+
+    /// Replays the first-parent commits of `tip` missing from `onto` on top of `onto`, returns the new tip.
+    pub fn rebase_commits(&self, tip: Hash, onto: Hash) -> anyhow::Result<Hash> {
+        let mut new_tip = onto;
+        for (hash, Commit { prev, ts, author, comment, .. }) in self.branch_commits(tip, onto)? {
+            if prev == new_tip {
+                // already on top of the new base, keep the commit as is
+                new_tip = hash;
+                continue;
+            }
+            let outcome = self.merge_commits(prev, new_tip, hash)?;
+            if !outcome.conflicts.is_empty() {
+                return Err(MergeConflicts(outcome.conflicts).into());
+            }
+            if outcome.rev.is_empty() {
+                // the changes are already upstream
+                continue;
+            }
+            new_tip = self.commit(Commit { prev: new_tip, ts, author, comment, merge: None, merge_hash: None, rev: outcome.rev }, Vec::new(), RefOp::Rebase)?;
+        }
+        Ok(new_tip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{model::*, command::*, testutil::TestRepo};
+    use super::MergeConflicts;
+
+    fn rebase(repo: &TestRepo, ts: u64, branch: &Branch) -> anyhow::Result<()> {
+        repo.exec(ts, CommandInner::Rebase(CRebase { branch: branch.clone(), onto: Main }))
+    }
+
+    #[test]
+    fn merge_fast_forward() {
+        let repo = TestRepo::new("merge-ff", RepoConfig::default());
+        let root = repo.commit_pages(&Main, 1, &[("a", "a\n")]);
+        let branch = repo.branch_at(2, root);
+        let theirs = repo.commit_pages(&branch, 3, &[("a", "b\n")]);
+        repo.merge(4, &branch, &Main).unwrap();
+        let merge = repo.get_commit(repo.get_ref(&Main).unwrap()).unwrap();
+        assert_eq!(merge.prev, theirs);
+        assert!(merge.rev.is_empty());
+        assert_eq!(merge.merge, Some(branch.clone()));
+        assert_eq!(repo.page_at(&Main, "a").as_deref(), Some("b\n"));
+        assert!(repo.merge(5, &branch, &Main).is_err());
+    }
+
+    #[test]
+    fn merge_three_way() {
+        let repo = TestRepo::new("merge-3way", RepoConfig::default());
+        let root = repo.commit_pages(&Main, 1, &[("a", "a\n"), ("b", "b\n")]);
+        let branch = repo.branch_at(2, root);
+        let theirs = repo.commit_pages(&branch, 3, &[("b", "b2\n"), ("c", "c\n")]);
+        let ours = repo.commit_pages(&Main, 4, &[("a", "a2\n")]);
+        repo.merge(5, &branch, &Main).unwrap();
+        let merge = repo.get_commit(repo.get_ref(&Main).unwrap()).unwrap();
+        assert_eq!(merge.parents(), vec![ours, theirs]);
+        assert_eq!(repo.page_at(&Main, "a").as_deref(), Some("a2\n"));
+        assert_eq!(repo.page_at(&Main, "b").as_deref(), Some("b2\n"));
+        assert_eq!(repo.page_at(&Main, "c").as_deref(), Some("c\n"));
+        // the branch is left as it was
+        assert_eq!(repo.page_at(&branch, "a").as_deref(), Some("a\n"));
+    }
+
+    #[test]
+    fn merge_conflict() {
+        let repo = TestRepo::new("merge-conflict", RepoConfig::default());
+        let root = repo.commit_pages(&Main, 1, &[("a", "a\n"), ("b", "b\n")]);
+        let branch = repo.branch_at(2, root);
+        repo.commit_pages(&branch, 3, &[("a", "theirs\n"), ("b", "b2\n")]);
+        let ours = repo.commit_pages(&Main, 4, &[("a", "ours\n")]);
+        let err = repo.merge(5, &branch, &Main).unwrap_err();
+        let MergeConflicts(conflicts) = err.downcast::<MergeConflicts>().unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].object_kind, conflicts[0].path.as_str()), (ObjectKind::Page, "a"));
+        // nothing is committed, not even the paths that merge cleanly
+        assert_eq!(repo.get_ref(&Main).unwrap(), ours);
+    }
+
+    #[test]
+    fn merge_base_after_criss_cross() {
+        let repo = TestRepo::new("criss-cross", RepoConfig::default());
+        let root = repo.commit_pages(&Main, 1, &[("a", "a\n"), ("b", "b\n")]);
+        let (x, y) = (repo.branch_at(2, root), repo.branch_at(3, root));
+        let x1 = repo.commit_pages(&x, 4, &[("a", "x\n")]);
+        let y1 = repo.commit_pages(&y, 5, &[("b", "y\n")]);
+        // keeps `x1` to be merged into `y` after `x` has moved on
+        let x_at_x1 = repo.branch_at(6, x1);
+        repo.merge(7, &y, &x).unwrap();
+        repo.merge(8, &x_at_x1, &y).unwrap();
+        let (x2, y2) = (repo.get_ref(&x).unwrap(), repo.get_ref(&y).unwrap());
+        let mut bases = repo.merge_bases(x2, y2).unwrap();
+        bases.sort();
+        let mut expected = vec![x1, y1];
+        expected.sort();
+        assert_eq!(bases, expected);
+        // the latest of the two
+        assert_eq!(repo.merge_base(x2, y2).unwrap(), y1);
+        repo.commit_pages(&x, 9, &[("c", "c\n")]);
+        repo.merge(10, &x, &y).unwrap();
+        assert_eq!(repo.page_at(&y, "c").as_deref(), Some("c\n"));
+        assert_eq!(repo.page_at(&y, "a").as_deref(), Some("x\n"));
+        assert_eq!(repo.page_at(&y, "b").as_deref(), Some("y\n"));
+    }
+
+    #[test]
+    fn rebases_replaying_the_same_commit() {
+        let repo = TestRepo::new("rebase-twice", RepoConfig::default());
+        let root = repo.commit_pages(&Main, 1, &[("a", "a\n")]);
+        let b1 = repo.branch_at(2, root);
+        let c1 = repo.commit_pages(&b1, 3, &[("b", "b\n")]);
+        let b2 = repo.branch_at(4, c1);
+        repo.commit_pages(&b2, 5, &[("c", "c\n")]);
+        repo.commit_pages(&Main, 6, &[("a", "a2\n")]);
+        rebase(&repo, 7, &b1).unwrap();
+        // replays `c1` onto the same tip again, which gives the commit made for `b1`
+        rebase(&repo, 8, &b2).unwrap();
+        let replayed = repo.get_ref(&b1).unwrap();
+        assert_eq!(repo.get_commit(repo.get_ref(&b2).unwrap()).unwrap().prev, replayed);
+        let mut paths: Vec<String> = repo.get_state(repo.get_ref(&b2).unwrap()).unwrap().page.into_keys().collect();
+        paths.sort();
+        assert_eq!(paths, ["a", "b", "c"]);
+    }
+}
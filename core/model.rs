@@ -11,9 +11,22 @@ pub struct Commit {
     pub author: String,
     pub comment: String,
     pub merge: Option<Branch>,
+    /// tip of `merge` at the time it was merged, absent in commits written before it was recorded
+    #[serde(default, with = "serde_bytes")]
+    pub merge_hash: Option<Hash>,
     pub rev: Vec<Rev>,
 }
 
+impl Commit {
+    pub fn parents(&self) -> Vec<Hash> {
+        let mut parents = vec![self.prev];
+        if let Some(hash) = self.merge_hash {
+            parents.push(hash);
+        }
+        parents
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rev {
     #[serde(flatten)]
@@ -22,7 +35,7 @@ pub struct Rev {
     pub path: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum ObjectKind {
     Data,
@@ -48,6 +61,15 @@ pub enum RevInner {
     Remove,
 }
 
+impl RevInner {
+    pub fn from_hash(hash: Option<Hash>) -> RevInner {
+        match hash {
+            Some(hash) => RevInner::Update { hash },
+            None => RevInner::Remove,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum RevKind {
@@ -55,7 +77,7 @@ pub enum RevKind {
     Remove,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", tag = "kind")]
 pub enum Branch {
     Main,
@@ -65,7 +87,7 @@ pub enum Branch {
 
 pub use Branch::Main;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CommonBranch {
     pub ts: u64,
     pub author: String,
@@ -79,11 +101,95 @@ impl Branch {
             Branch::Common(CommonBranch { ts, author }) => format!("{}-{}", ts, author),
         }
     }
+
+    /// Inverse of `to_string`, a named branch that looks like `<ts>-<author>` reads back as common.
+    pub fn from_string(name: &str) -> Branch {
+        if name == "main" {
+            return Branch::Main;
+        }
+        if let Some((ts, author)) = name.split_once('-') {
+            if let (Ok(ts), false) = (ts.parse(), author.is_empty()) {
+                return Branch::Common(CommonBranch { ts, author: author.to_owned() });
+            }
+        }
+        Branch::Named(name.to_owned())
+    }
 }
 
-pub type StateMap = HashMap<String, Hash>;
+#[derive(Debug, Clone)]
+pub struct BranchInfo {
+    pub branch: Branch,
+    /// the commit the branch was created at
+    pub root: Hash,
+    pub tip: Hash,
+    /// times the ref moved after creation
+    pub updates: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RefOp {
+    Create,
+    Commit,
+    Merge,
+    Revert,
+    CherryPick,
+    Rebase,
+    Reset,
+    /// written before ref updates were logged
+    Legacy,
+}
+
+impl RefOp {
+    pub fn to_sign(&self) -> &'static str {
+        match self {
+            RefOp::Create => "create",
+            RefOp::Commit => "commit",
+            RefOp::Merge => "merge",
+            RefOp::Revert => "revert",
+            RefOp::CherryPick => "cherry-pick",
+            RefOp::Rebase => "rebase",
+            RefOp::Reset => "reset",
+            RefOp::Legacy => "legacy",
+        }
+    }
+
+    pub fn from_sign(sign: &str) -> Option<RefOp> {
+        Some(match sign {
+            "create" => RefOp::Create,
+            "commit" => RefOp::Commit,
+            "merge" => RefOp::Merge,
+            "revert" => RefOp::Revert,
+            "cherry-pick" => RefOp::CherryPick,
+            "rebase" => RefOp::Rebase,
+            "reset" => RefOp::Reset,
+            "legacy" => RefOp::Legacy,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefLogEntry {
+    #[serde(with = "serde_bytes")]
+    pub hash: Hash,
+    pub ts: u64,
+    pub author: String,
+    pub op: RefOp,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tag {
+    #[serde(with = "serde_bytes")]
+    pub hash: Hash,
+    pub ts: u64,
+    pub author: String,
+    pub message: String,
+}
+
+pub type StateMap = HashMap<String, Hash>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct State {
     pub data: StateMap,
     pub page: StateMap,
@@ -93,16 +199,38 @@ impl State {
     pub fn empty() -> State {
         State { data: HashMap::new(), page: HashMap::new() }
     }
+
+    pub fn map(&self, object_kind: ObjectKind) -> &StateMap {
+        match object_kind {
+            ObjectKind::Data => &self.data,
+            ObjectKind::Page => &self.page,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "kind")]
+pub enum Compression {
+    #[default]
+    None,
+    Zstd { level: i32 },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RepoConfig {
     pub version: String,
+    // toml needs plain values before tables
+    /// longest chain of deltas a page may be stored at the end of, 0 stores every page in full
+    #[serde(default)]
+    pub page_delta_depth: u32,
     pub online_branch: Branch,
+    /// applies to blobs written from now on, existing ones stay readable either way
+    #[serde(default)]
+    pub compression: Compression,
 }
 
 impl Default for RepoConfig {
     fn default() -> RepoConfig {
-        RepoConfig { version: VERSION.to_owned(), online_branch: Main }
+        RepoConfig { version: VERSION.to_owned(), page_delta_depth: 0, online_branch: Main, compression: Compression::None }
     }
 }
@@ -0,0 +1,200 @@
+use crate::{prelude::*, repo::BlobKind};
+
+// pack file: `PACK_MAGIC`, then the stored bytes of each blob back to back
+// index file: `INDEX_MAGIC`, then one entry per blob sorted by kind and hash,
+// `<kind: u8> <hash> <offset: u64 be> <len: u64 be>`
+
+const PACK_MAGIC: &[u8; 8] = b"LBPACK01";
+const INDEX_MAGIC: &[u8; 8] = b"LBIDX001";
+const KEY_LEN: usize = 1 + HASH_LEN;
+const ENTRY_LEN: usize = KEY_LEN + 8 + 8;
+
+pub const PACK_EXT: &str = "pack";
+pub const INDEX_EXT: &str = "idx";
+
+fn kind_to_byte(kind: BlobKind) -> u8 {
+    match kind {
+        BlobKind::Data => 0,
+        BlobKind::Page => 1,
+        BlobKind::Commit => 2,
+        BlobKind::State => 3,
+    }
+}
+
+fn byte_to_kind(byte: u8) -> Option<BlobKind> {
+    Some(match byte {
+        0 => BlobKind::Data,
+        1 => BlobKind::Page,
+        2 => BlobKind::Commit,
+        3 => BlobKind::State,
+        _ => return None,
+    })
+}
+
+fn entry_key(kind: BlobKind, hash: Hash) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    key[0] = kind_to_byte(kind);
+    key[1..].copy_from_slice(&hash);
+    key
+}
+
+/// `(offset, len)` of the blob in the pack.
+fn entry_location(entry: &[u8]) -> (u64, u64) {
+    let offset = u64::from_be_bytes(entry[KEY_LEN..KEY_LEN + 8].try_into().unwrap());
+    let len = u64::from_be_bytes(entry[KEY_LEN + 8..].try_into().unwrap());
+    (offset, len)
+}
+
+/// A pack whose index is held in memory, blobs are read from disk on demand.
+pub struct Pack {
+    path: PathBuf,
+    /// index entries without the magic
+    index: Vec<u8>,
+}
+
+impl Pack {
+    /// `path` is the pack file, its index is expected next to it.
+    pub fn open(path: PathBuf) -> io::Result<Pack> {
+        let index = fs::read(path.with_extension(INDEX_EXT))?;
+        let pack_len = fs::metadata(&path)?.len();
+        // a corrupt entry must not make `read` seek or allocate past the pack
+        let is_in_pack = |entry: &[u8]| {
+            let (offset, len) = entry_location(entry);
+            offset >= PACK_MAGIC.len() as u64 && offset.checked_add(len).is_some_and(|end| end <= pack_len)
+        };
+        let is_valid = index.starts_with(INDEX_MAGIC)
+            && index[INDEX_MAGIC.len()..].chunks_exact(ENTRY_LEN).remainder().is_empty()
+            && index[INDEX_MAGIC.len()..].chunks(ENTRY_LEN).all(|entry| byte_to_kind(entry[0]).is_some() && is_in_pack(entry));
+        if !is_valid {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid pack index {}", path.display())));
+        }
+        Ok(Pack { index: index[INDEX_MAGIC.len()..].to_vec(), path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len() / ENTRY_LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    fn entry(&self, i: usize) -> &[u8] {
+        &self.index[i * ENTRY_LEN..(i + 1) * ENTRY_LEN]
+    }
+
+    fn find(&self, kind: BlobKind, hash: Hash) -> Option<(u64, u64)> {
+        let key = entry_key(kind, hash);
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let entry = self.entry(mid);
+            match entry[..KEY_LEN].cmp(&key[..]) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(entry_location(entry)),
+            }
+        }
+        None
+    }
+
+    pub fn contains(&self, kind: BlobKind, hash: Hash) -> bool {
+        self.find(kind, hash).is_some()
+    }
+
+    /// Stored bytes of a blob, `None` if it is not in this pack.
+    pub fn read(&self, kind: BlobKind, hash: Hash) -> io::Result<Option<Vec<u8>>> {
+        let (offset, len) = match self.find(kind, hash) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let mut file = OpenOptions::new().read(true).open(&self.path)?;
+        file.seek(io::SeekFrom::Start(offset))?;
+        let mut blob = vec![0u8; len as usize];
+        file.read_exact(&mut blob)?;
+        Ok(Some(blob))
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (BlobKind, Hash)> + '_ {
+        self.index.chunks(ENTRY_LEN).map(|entry| (byte_to_kind(entry[0]).unwrap(), entry[1..KEY_LEN].try_into().unwrap()))
+    }
+}
+
+/// Appends blobs to a new pack file, the index is built by `finish`.
+pub struct PackWriter {
+    file: fs::File,
+    offset: u64,
+    entries: BTreeMap<[u8; KEY_LEN], (u64, u64)>,
+}
+
+impl PackWriter {
+    pub fn create(path: &Path) -> io::Result<PackWriter> {
+        let mut file = OpenOptions::new().create_new(true).write(true).open(path)?;
+        file.write_all(PACK_MAGIC)?;
+        Ok(PackWriter { file, offset: PACK_MAGIC.len() as u64, entries: BTreeMap::new() })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, kind: BlobKind, hash: Hash) -> bool {
+        self.entries.contains_key(&entry_key(kind, hash))
+    }
+
+    /// A blob already added is skipped.
+    pub fn add(&mut self, kind: BlobKind, hash: Hash, blob: &[u8]) -> io::Result<()> {
+        if self.contains(kind, hash) {
+            return Ok(());
+        }
+        self.file.write_all(blob)?;
+        self.entries.insert(entry_key(kind, hash), (self.offset, blob.len() as u64));
+        self.offset += blob.len() as u64;
+        Ok(())
+    }
+
+    /// Syncs the pack file and returns the content of its index.
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        self.file.sync_all()?;
+        let mut index = Vec::with_capacity(INDEX_MAGIC.len() + self.entries.len() * ENTRY_LEN);
+        index.extend_from_slice(INDEX_MAGIC);
+        for (key, (offset, len)) in self.entries {
+            index.extend_from_slice(&key);
+            index.extend_from_slice(&offset.to_be_bytes());
+            index.extend_from_slice(&len.to_be_bytes());
+        }
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_past_the_pack_is_invalid() {
+        let dir = std::env::temp_dir().join(format!("lesserbase-pack-{}-{}", std::process::id(), now()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test").with_extension(PACK_EXT);
+        let mut writer = PackWriter::create(&path).unwrap();
+        writer.add(BlobKind::Data, hash_all(b"blob"), b"blob").unwrap();
+        let mut index = writer.finish().unwrap();
+        fs::write(path.with_extension(INDEX_EXT), &index).unwrap();
+        assert_eq!(Pack::open(path.clone()).unwrap().read(BlobKind::Data, hash_all(b"blob")).unwrap().unwrap(), b"blob");
+        let len_at = INDEX_MAGIC.len() + KEY_LEN + 8;
+        for len in [5, u64::MAX] {
+            index[len_at..len_at + 8].copy_from_slice(&len.to_be_bytes());
+            fs::write(path.with_extension(INDEX_EXT), &index).unwrap();
+            assert_eq!(Pack::open(path.clone()).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
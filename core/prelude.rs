@@ -2,7 +2,7 @@ pub use std::{
     path::{Path, PathBuf},
     fs::{self, OpenOptions},
     io::{self, Read, Write, Seek, BufRead, BufReader},
-    collections::HashMap,
+    collections::{HashMap, HashSet, BTreeMap, BTreeSet},
     sync::mpsc::{channel, Sender, Receiver},
 };
 pub use serde::{Serialize, Deserialize};
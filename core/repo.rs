@@ -1,4 +1,5 @@
-use crate::{prelude::*, model::*};
+use std::{borrow::Cow, sync::{Arc, RwLock}};
+use crate::{prelude::*, model::*, pack::{Pack, PackWriter, PACK_EXT, INDEX_EXT}, delta::PageDelta};
 
 impl State {
     pub fn update(&mut self, rev: Vec<Rev>) {
@@ -15,6 +16,37 @@ impl State {
     }
 }
 
+/// Content-addressed files, named by the hash of their content, or of their commit for states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlobKind {
+    Data,
+    Page,
+    Commit,
+    State,
+}
+
+impl BlobKind {
+    pub const ALL: [BlobKind; 4] = [BlobKind::Data, BlobKind::Page, BlobKind::Commit, BlobKind::State];
+
+    pub fn to_sign(&self) -> &'static str {
+        match self {
+            BlobKind::Data => "data",
+            BlobKind::Page => "page",
+            BlobKind::Commit => "commit",
+            BlobKind::State => "state",
+        }
+    }
+}
+
+impl From<ObjectKind> for BlobKind {
+    fn from(object_kind: ObjectKind) -> BlobKind {
+        match object_kind {
+            ObjectKind::Data => BlobKind::Data,
+            ObjectKind::Page => BlobKind::Page,
+        }
+    }
+}
+
 struct PathBuilder {
     root: PathBuf,
     config: PathBuf,
@@ -24,6 +56,12 @@ struct PathBuilder {
     commits: PathBuf,
     states: PathBuf,
     refs: PathBuf,
+    archive: PathBuf,
+    tags: PathBuf,
+    locks: PathBuf,
+    quarantine: PathBuf,
+    packs: PathBuf,
+    tmp: PathBuf,
 }
 
 impl PathBuilder {
@@ -36,6 +74,12 @@ impl PathBuilder {
             commits: (&root).join("commits"),
             states: (&root).join("states"),
             refs: (&root).join("refs"),
+            archive: root.join("archive"),
+            tags: root.join("tags"),
+            locks: root.join("locks"),
+            quarantine: root.join("quarantine"),
+            packs: root.join("packs"),
+            tmp: root.join("tmp"),
             root, objects,
         }
     }
@@ -56,9 +100,55 @@ impl PathBuilder {
         self.states.join(hash_to_hex(hash).as_ref())
     }
 
+    fn blob_dir(&self, kind: BlobKind) -> &PathBuf {
+        match kind {
+            BlobKind::Data => &self.data_objects,
+            BlobKind::Page => &self.page_objects,
+            BlobKind::Commit => &self.commits,
+            BlobKind::State => &self.states,
+        }
+    }
+
+    fn blob(&self, kind: BlobKind, hash: Hash) -> PathBuf {
+        self.blob_dir(kind).join(hash_to_hex(hash).as_ref())
+    }
+
+    fn quarantined(&self, kind: BlobKind, hash: Hash) -> PathBuf {
+        self.quarantine.join(kind.to_sign()).join(hash_to_hex(hash).as_ref())
+    }
+
+    fn pack(&self, name: &str) -> PathBuf {
+        self.packs.join(name).with_extension(PACK_EXT)
+    }
+
+    /// Held while packs are rewritten, however long that takes, kept out of `locks/` where any name may be a branch.
+    fn pack_lock(&self) -> PathBuf {
+        self.packs.join("lock")
+    }
+
+    /// Unique per writer, so that concurrent writes of the same blob do not collide.
+    /// Kept out of the dirs written to, where names are chosen by users and any may be a ref or tag.
+    fn tmp_file(&self) -> PathBuf {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        self.tmp.join(format!("{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)))
+    }
+
     fn aref(&self, branch: &Branch) -> PathBuf {
         self.refs.join(branch.to_string())
     }
+
+    fn archived_ref(&self, branch: &Branch) -> PathBuf {
+        self.archive.join(branch.to_string())
+    }
+
+    fn tag(&self, name: &str) -> PathBuf {
+        self.tags.join(name)
+    }
+
+    fn ref_lock(&self, branch: &Branch) -> PathBuf {
+        self.locks.join(branch.to_string())
+    }
 }
 
 macro_rules! path_builder_get_impl {
@@ -81,6 +171,12 @@ path_builder_get_impl!(
     commits,
     states,
     refs,
+    archive,
+    tags,
+    locks,
+    quarantine,
+    packs,
+    tmp,
 );
 
 pub enum DbOp {
@@ -88,28 +184,221 @@ pub enum DbOp {
     AddPageObject { hash: Hash, content: String },
     AddCommit { hash: Hash, commit: Commit },
     AddState { hash: Hash, state: State },
-    CreateRef { branch: Branch, hash: Hash },
-    UpdateRef { branch: Branch, hash: Hash },
+    CreateRef { branch: Branch, entry: RefLogEntry },
+    UpdateRef { branch: Branch, entry: RefLogEntry },
+    DeleteRef { branch: Branch },
+    ArchiveRef { branch: Branch },
+    CreateTag { name: String, tag: Tag },
 }
 
 type DbTx = Sender<DbOp>;
 type DbRx = Receiver<DbOp>;
 
+#[derive(Default)]
+struct LoadedPacks {
+    packs: Vec<Arc<Pack>>,
+    /// mtime of `packs/` when loaded, `None` if too recent to tell later changes apart by it
+    dir_modified: Option<std::time::SystemTime>,
+}
+
+/// A directory mtime this close to now may still be shared with a change not yet seen.
+const PACKS_MTIME_RESOLUTION: std::time::Duration = std::time::Duration::from_secs(1);
+
 pub struct Repo {
     config: RepoConfig,
     path: PathBuilder,
     db_tx: DbTx,
+    /// loaded again when `packs/` changed, another process may have repacked
+    packs: RwLock<LoadedPacks>,
 }
 
 use fs::read as read_blob;
 
-fn write_blob<P: AsRef<Path>>(path: P, blob: &[u8]) -> io::Result<()> {
-    // TODO: err: hash collision
-    let mut file = OpenOptions::new().create_new(true).write(true).open(path)?;
+#[derive(Debug)]
+pub struct StaleRef {
+    pub branch: Branch,
+    pub expected: Hash,
+    pub actual: Hash,
+}
+
+impl std::fmt::Display for StaleRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "stale prev for {}: expected {}, actual {}", self.branch.to_string(), hash_to_hex(self.expected), hash_to_hex(self.actual))
+    }
+}
+
+impl std::error::Error for StaleRef {}
+
+const REF_LOCK_RETRY: u32 = 100;
+const REF_LOCK_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Held while a ref file is read and written, released when dropped or when the holder dies.
+struct RefLock {
+    file: fs::File,
+}
+
+impl RefLock {
+    fn acquire(path: PathBuf) -> anyhow::Result<RefLock> {
+        // the file stays, removing it would let a waiter lock the unlinked file while another locks a new one
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+        for _ in 0..REF_LOCK_RETRY {
+            match file.try_lock() {
+                Ok(()) => return Ok(RefLock { file }),
+                Err(fs::TryLockError::WouldBlock) => std::thread::sleep(REF_LOCK_RETRY_INTERVAL),
+                Err(fs::TryLockError::Error(err)) => return Err(err.into()),
+            }
+        }
+        Err(anyhow::anyhow!("ref lock {} is held by another writer", path.display()))
+    }
+}
+
+// region: crash-safe writes
+
+/// Leftovers younger than this may still belong to a live writer.
+const RECOVER_MIN_AGE: std::time::Duration = std::time::Duration::from_secs(60);
+
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    fs::File::open(path.parent().unwrap())?.sync_all()
+}
+
+/// Readers see either no file at `path` or all of `blob`, never a prefix of it.
+/// `tmp` must be on the same file system as `path`.
+fn write_file_atomic(tmp: PathBuf, path: &Path, blob: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().create_new(true).write(true).open(&tmp)?;
+    file.write_all(blob)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    sync_parent_dir(path)
+}
+
+/// Like `write_file_atomic`, but fails with `AlreadyExists` instead of replacing an existing file.
+fn write_file_new(tmp: PathBuf, path: &Path, blob: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().create_new(true).write(true).open(&tmp)?;
     file.write_all(blob)?;
+    file.sync_all()?;
+    // unlike rename, linking never replaces the target
+    let linked = fs::hard_link(&tmp, path);
+    fs::remove_file(&tmp)?;
+    linked?;
+    sync_parent_dir(path)
+}
+
+fn write_blob<P: AsRef<Path>>(tmp: PathBuf, path: P, blob: &[u8]) -> io::Result<()> {
+    // TODO: err: hash collision
+    let path = path.as_ref();
+    // saves writing a temp file for the common case, `write_file_new` decides under races
+    if file_detected(path)? {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", path.display())));
+    }
+    write_file_new(tmp, path, blob)
+}
+
+/// Like `write_blob`, but an existing blob is fine as objects are addressed by content.
+/// Returns whether the blob was newly written.
+fn write_object(tmp: PathBuf, path: PathBuf, blob: &[u8]) -> io::Result<bool> {
+    match write_blob(tmp, &path, blob) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            // an unreachable blob being reused must not look old to a concurrent gc
+            OpenOptions::new().write(true).open(&path)?.set_modified(std::time::SystemTime::now())?;
+            Ok(false)
+        },
+        Err(err) => Err(err),
+    }
+}
+
+// canonical blobs never start with it: in msgpack it is a lone int followed by more bytes, in UTF-8 it is invalid
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+fn compress_blob(compression: Compression, blob: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    match compression {
+        Compression::None => Ok(Cow::Borrowed(blob)),
+        Compression::Zstd { level } => {
+            let compressed = zstd::encode_all(blob, level)?;
+            // tiny blobs can grow, those are kept as they are
+            Ok(if compressed.len() < blob.len() { Cow::Owned(compressed) } else { Cow::Borrowed(blob) })
+        },
+    }
+}
+
+/// Canonical bytes of a stored blob, whatever compression it was written with.
+fn decompress_blob(stored: Vec<u8>) -> io::Result<Vec<u8>> {
+    if stored.starts_with(&ZSTD_MAGIC) {
+        zstd::decode_all(stored.as_slice())
+    } else {
+        Ok(stored)
+    }
+}
+
+fn is_leftover(path: &Path) -> io::Result<bool> {
+    Ok(matches!(fs::metadata(path)?.modified()?.elapsed(), Ok(age) if age >= RECOVER_MIN_AGE))
+}
+
+// endregion
+
+// region: ref file format
+
+// one line per update: `<hex hash> <ts> <op> <author>`
+
+const REF_TAIL_LEN: u64 = 4096;
+
+/// Authors end up as the last field of a ref line, a line break would split the entry.
+pub fn check_ref_author(author: &str) -> anyhow::Result<()> {
+    if author.contains(['\n', '\r']) {
+        return Err(anyhow::anyhow!("invalid author {:?}", author));
+    }
     Ok(())
 }
 
+fn format_ref_line(entry: &RefLogEntry) -> String {
+    let RefLogEntry { hash, ts, author, op } = entry;
+    format!("{} {} {} {}\n", hash_to_hex(*hash), ts, op.to_sign(), author)
+}
+
+fn parse_ref_line(line: &str) -> anyhow::Result<RefLogEntry> {
+    let mut iter = line.splitn(4, ' ');
+    let hash = hex_to_hash(iter.next().unwrap())?;
+    match (iter.next(), iter.next(), iter.next()) {
+        // bare hash written before ref updates were logged
+        (None, _, _) => Ok(RefLogEntry { hash, ts: 0, author: String::new(), op: RefOp::Legacy }),
+        (Some(ts), Some(op), Some(author)) => Ok(RefLogEntry {
+            hash,
+            ts: ts.parse()?,
+            author: author.to_owned(),
+            op: RefOp::from_sign(op).ok_or_else(|| anyhow::anyhow!("unknown ref op {}", op))?,
+        }),
+        _ => Err(anyhow::anyhow!("invalid ref line {:?}", line)),
+    }
+}
+
+fn read_reflog(path: PathBuf) -> anyhow::Result<Vec<RefLogEntry>> {
+    let file = OpenOptions::new().read(true).open(path)?;
+    BufReader::new(file).lines().map(|line| parse_ref_line(&line?)).collect()
+}
+
+fn read_last_ref(path: PathBuf) -> anyhow::Result<RefLogEntry> {
+    let mut file = OpenOptions::new().read(true).open(path)?;
+    let mut end = file.metadata()?.len();
+    let mut buf = Vec::new();
+    // read backwards until the whole last line is in the buffer
+    loop {
+        let start = end.saturating_sub(REF_TAIL_LEN);
+        let mut block = vec![0u8; (end - start) as usize];
+        file.seek(io::SeekFrom::Start(start))?;
+        file.read_exact(&mut block)?;
+        block.extend(buf);
+        buf = block;
+        end = start;
+        if end == 0 || buf[..buf.len().saturating_sub(1)].contains(&b'\n') {
+            break;
+        }
+    }
+    let buf = String::from_utf8(buf)?;
+    parse_ref_line(buf.trim_end_matches('\n').rsplit('\n').next().unwrap())
+}
+
+// endregion
+
 impl Repo {
     pub fn new(path: PathBuf) -> anyhow::Result<(Repo, DbRx)> {
         let path = PathBuilder::new(path);
@@ -119,10 +408,16 @@ impl Repo {
                 Err(anyhow::anyhow!("config version {} != currect version {}", config.version, VERSION))
             } else {
                 let (db_tx, db_rx) = channel();
-                let repo = Repo { config, path, db_tx };
+                let repo = Repo { config, path, db_tx, packs: RwLock::default() };
                 if !file_detected(&repo.path.aref(&repo.config.online_branch))? {
                     repo.init()?;
+                } else {
+                    // repos created by older versions may lack newer dirs
+                    repo.init_dirs()?;
                 }
+                repo.recover()?;
+                repo.migrate_refs()?;
+                repo.load_packs()?;
                 Ok((repo, db_rx))
             }
         } else {
@@ -134,32 +429,112 @@ impl Repo {
         fs::create_dir_all(&path)?;
         let path = PathBuilder::new(path);
         let config = RepoConfig::default();
-        write_blob(path.config(), &toml::to_vec(&config)?)?;
+        fs::create_dir_all(path.tmp())?;
+        write_blob(path.tmp_file(), path.config(), &toml::to_vec(&config)?)?;
         let (db_tx, db_rx) = channel();
-        let repo = Repo { config, path, db_tx };
+        let repo = Repo { config, path, db_tx, packs: RwLock::default() };
         repo.init()?;
         Ok((repo, db_rx))
     }
 
-    pub fn init(&self) -> io::Result<()> {
+    fn init_dirs(&self) -> io::Result<()> {
         fs::create_dir_all(self.path.data_objects())?;
         fs::create_dir_all(self.path.page_objects())?;
         fs::create_dir_all(self.path.commits())?;
         fs::create_dir_all(self.path.states())?;
         fs::create_dir_all(self.path.refs())?;
-        self.fs_create_ref(&self.config.online_branch, EMPTY_HASH)?;
+        fs::create_dir_all(self.path.archive())?;
+        fs::create_dir_all(self.path.tags())?;
+        fs::create_dir_all(self.path.locks())?;
+        fs::create_dir_all(self.path.packs())?;
+        fs::create_dir_all(self.path.tmp())?;
         Ok(())
     }
 
+    pub fn init(&self) -> anyhow::Result<()> {
+        self.init_dirs()?;
+        // the empty state is the merge base of every two unrelated histories
+        if !file_detected(&self.path.state(EMPTY_HASH))? {
+            self.write_stored_blob(BlobKind::State, EMPTY_HASH, &rmp_serde::to_vec_named(&State::empty())?)?;
+        }
+        let entry = RefLogEntry { hash: EMPTY_HASH, ts: now(), author: String::new(), op: RefOp::Create };
+        self.fs_create_ref(&self.config.online_branch, &entry)?;
+        Ok(())
+    }
+
+    /// Rewrites ref files of older versions, which hold bare hashes, into the logged format.
+    pub fn migrate_refs(&self) -> anyhow::Result<()> {
+        for dir in [self.path.refs(), self.path.archive()] {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let raw = fs::read_to_string(&path)?;
+                if raw.lines().all(|line| line.contains(' ')) {
+                    continue;
+                }
+                let mut migrated = String::new();
+                for line in raw.lines() {
+                    migrated.push_str(&format_ref_line(&parse_ref_line(line)?));
+                }
+                write_file_atomic(self.path.tmp_file(), &path, migrated.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Cleans up after a writer that crashed mid-way, see `commit` for the write order.
+    pub fn recover(&self) -> anyhow::Result<()> {
+        for entry in fs::read_dir(self.path.tmp())? {
+            let path = entry?.path();
+            if is_leftover(&path)? {
+                fs::remove_file(&path)?;
+            }
+        }
+        for entry in fs::read_dir(self.path.packs())? {
+            let path = entry?.path();
+            // a pack is complete only once its index is written
+            let is_pack = path.extension().is_some_and(|ext| ext == PACK_EXT);
+            if is_pack && !file_detected(&path.with_extension(INDEX_EXT))? && is_leftover(&path)? {
+                fs::remove_file(&path)?;
+            }
+        }
+        for dir in [self.path.refs(), self.path.archive()] {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let is_partial = |raw: &[u8]| !raw.is_empty() && !raw.ends_with(b"\n");
+                if !is_partial(&fs::read(&path)?) {
+                    continue;
+                }
+                // drop a half-written last line, unless a live writer completes it meanwhile
+                let _lock = RefLock::acquire(self.path.locks().join(path.file_name().unwrap()))?;
+                let raw = fs::read(&path)?;
+                if is_partial(&raw) {
+                    let len = raw.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+                    let file = OpenOptions::new().write(true).open(&path)?;
+                    file.set_len(len as u64)?;
+                    file.sync_all()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn config(&self) -> &RepoConfig {
+        &self.config
+    }
+
     // region: get from fs
 
     pub fn get_ref(&self, branch: &Branch) -> anyhow::Result<Hash> {
-        let mut file = OpenOptions::new().read(true).open(self.path.aref(branch))?;
-        file.seek(io::SeekFrom::End(-(HASH_LEN_I64 * 2 + 1)))?;
-        let mut buf = String::new();
-        file.read_to_string(&mut buf)?;
-        assert_eq!(buf.len(), HASH_LEN * 2 + 1);
-        Ok(hex_to_hash(&buf[0..HASH_LEN * 2])?)
+        Ok(read_last_ref(self.path.aref(branch))?.hash)
+    }
+
+    /// Fails with `StaleRef` if the branch has moved away from `expected`.
+    pub fn check_ref(&self, branch: &Branch, expected: Hash) -> anyhow::Result<()> {
+        let actual = self.get_ref(branch)?;
+        if actual != expected {
+            return Err(StaleRef { branch: branch.clone(), expected, actual }.into());
+        }
+        Ok(())
     }
 
     pub fn get_root_ref(&self, branch: &Branch) -> anyhow::Result<Hash> {
@@ -170,48 +545,225 @@ impl Repo {
     }
 
     pub fn get_all_ref(&self, branch: &Branch) -> anyhow::Result<Vec<Hash>> {
-        let file = OpenOptions::new().read(true).open(self.path.aref(branch))?;
-        let buf = BufReader::new(file);
+        Ok(self.get_reflog(branch)?.into_iter().map(|entry| entry.hash).collect())
+    }
+
+    pub fn get_all_archived_ref(&self, branch: &Branch) -> anyhow::Result<Vec<Hash>> {
+        Ok(self.get_archived_reflog(branch)?.into_iter().map(|entry| entry.hash).collect())
+    }
+
+    /// Every update of the ref, oldest first.
+    pub fn get_reflog(&self, branch: &Branch) -> anyhow::Result<Vec<RefLogEntry>> {
+        read_reflog(self.path.aref(branch))
+    }
+
+    pub fn get_archived_reflog(&self, branch: &Branch) -> anyhow::Result<Vec<RefLogEntry>> {
+        read_reflog(self.path.archived_ref(branch))
+    }
+
+    /// Live branches sorted by name, archived ones are not included.
+    pub fn list_branches(&self) -> anyhow::Result<Vec<BranchInfo>> {
         let mut result = Vec::new();
-        for h in buf.lines() {
-            result.push(hex_to_hash(h?)?)
+        for entry in fs::read_dir(self.path.refs())? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().map_err(|_| anyhow::anyhow!("invalid ref name"))?;
+            let reflog = read_reflog(entry.path())?;
+            let (root, tip) = match (reflog.first(), reflog.last()) {
+                (Some(root), Some(tip)) => (root.hash, tip.hash),
+                _ => return Err(anyhow::anyhow!("empty ref {}", name)),
+            };
+            result.push((name.clone(), BranchInfo { branch: Branch::from_string(&name), root, tip, updates: reflog.len() - 1 }));
         }
+        result.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(result.into_iter().map(|(_, info)| info).collect())
+    }
+
+    /// Archived branches sorted by name.
+    pub fn list_archived_branches(&self) -> anyhow::Result<Vec<Branch>> {
+        let mut result = Vec::new();
+        for entry in fs::read_dir(self.path.archive())? {
+            result.push(entry?.file_name().into_string().map_err(|_| anyhow::anyhow!("invalid ref name"))?);
+        }
+        result.sort();
+        Ok(result.iter().map(|name| Branch::from_string(name)).collect())
+    }
+
+    pub fn get_tag(&self, name: &str) -> anyhow::Result<Tag> {
+        Ok(rmp_serde::from_slice(&read_blob(self.path.tag(name))?)?)
+    }
+
+    /// All tags sorted by name.
+    pub fn list_tags(&self) -> anyhow::Result<Vec<(String, Tag)>> {
+        let mut result = Vec::new();
+        // every file is a complete tag, those being written are still in `tmp/`
+        for entry in fs::read_dir(self.path.tags())? {
+            let name = entry?.file_name().into_string().map_err(|_| anyhow::anyhow!("invalid tag name"))?;
+            let tag = self.get_tag(&name)?;
+            result.push((name, tag));
+        }
+        result.sort_by(|(a, _), (b, _)| a.cmp(b));
         Ok(result)
     }
 
+    /// Canonical bytes of a blob, loose or packed, which for all kinds but states hash to `hash`.
+    pub fn get_blob(&self, kind: BlobKind, hash: Hash) -> anyhow::Result<Vec<u8>> {
+        let mut blob = self.get_stored_blob(kind, hash)?;
+        if kind != BlobKind::Page {
+            return Ok(blob);
+        }
+        // depths must decrease along the chain, so a broken one ends in an error instead of a loop;
+        // a base may be a full page before depth 0 is reached, if it was written again in full
+        let mut chain: Vec<PageDelta> = Vec::new();
+        while let Some(delta) = PageDelta::decode(&blob)? {
+            if delta.depth == 0 || chain.last().is_some_and(|last| delta.depth >= last.depth) {
+                return Err(anyhow::anyhow!("broken delta chain of page {}", hash_to_hex(hash)));
+            }
+            blob = self.get_stored_blob(BlobKind::Page, delta.base)?;
+            chain.push(delta);
+        }
+        let mut page = String::from_utf8(blob)?;
+        for delta in chain.iter().rev() {
+            page = delta.apply(&page)?;
+        }
+        Ok(page.into_bytes())
+    }
+
+    /// Decompressed but, for pages, possibly still a delta.
+    fn get_stored_blob(&self, kind: BlobKind, hash: Hash) -> io::Result<Vec<u8>> {
+        let stored = match read_blob(self.path.blob(kind, hash)) {
+            Err(err) if is_file_not_found(&err) => self.read_packed(kind, hash)?.ok_or(err)?,
+            result => result?,
+        };
+        decompress_blob(stored)
+    }
+
+    /// `None` if the page is stored in full.
+    pub fn get_page_delta(&self, hash: Hash) -> anyhow::Result<Option<PageDelta>> {
+        PageDelta::decode(&self.get_stored_blob(BlobKind::Page, hash)?)
+    }
+
+    pub fn has_blob(&self, kind: BlobKind, hash: Hash) -> io::Result<bool> {
+        Ok(file_detected(&self.path.blob(kind, hash))? || self.find_packed(kind, hash)?.is_some())
+    }
+
+    fn list_loose_blobs(&self, kind: BlobKind) -> io::Result<Vec<Hash>> {
+        let mut result = Vec::new();
+        for entry in fs::read_dir(self.path.blob_dir(kind))? {
+            let name = entry?.file_name();
+            if let Some(hash) = name.to_str().and_then(|name| hex_to_hash(name).ok()) {
+                result.push(hash);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Hashes of all stored blobs of `kind`, sorted, files not named by a hash are skipped.
+    pub fn list_blobs(&self, kind: BlobKind) -> anyhow::Result<Vec<Hash>> {
+        let mut result = self.list_loose_blobs(kind)?;
+        for pack in self.packs.read().unwrap().packs.iter() {
+            result.extend(pack.entries().filter(|(entry_kind, _)| *entry_kind == kind).map(|(_, hash)| hash));
+        }
+        // a blob may be both loose and packed until the next repack
+        result.sort();
+        result.dedup();
+        Ok(result)
+    }
+
+    /// For packed blobs this is when the pack was written.
+    pub fn get_blob_modified(&self, kind: BlobKind, hash: Hash) -> io::Result<std::time::SystemTime> {
+        match fs::metadata(self.path.blob(kind, hash)) {
+            Err(err) if is_file_not_found(&err) => match self.find_packed(kind, hash)? {
+                Some(pack) => fs::metadata(pack.path())?.modified(),
+                None => Err(err),
+            },
+            result => result?.modified(),
+        }
+    }
+
     pub fn get_data_object(&self, hash: Hash) -> anyhow::Result<Json> {
-        Ok(msgpack_to_json(msgpack_decode(read_blob(self.path.data_object(hash))?)?))
+        Ok(msgpack_to_json(msgpack_decode(self.get_blob(BlobKind::Data, hash)?)?))
     }
 
     pub fn get_page_object(&self, hash: Hash) -> anyhow::Result<String> {
-        Ok(String::from_utf8(read_blob(self.path.page_object(hash))?)?)
+        Ok(String::from_utf8(self.get_blob(BlobKind::Page, hash)?)?)
     }
 
     pub fn get_commit(&self, hash: Hash) -> anyhow::Result<Commit> {
-        Ok(rmp_serde::from_slice(&read_blob(self.path.commit(hash))?)?)
+        Ok(rmp_serde::from_slice(&self.get_blob(BlobKind::Commit, hash)?)?)
     }
 
     pub fn get_state(&self, hash: Hash) -> anyhow::Result<State> {
-        Ok(rmp_serde::from_slice(&read_blob(self.path.state(hash))?)?)
+        Ok(rmp_serde::from_slice(&self.get_blob(BlobKind::State, hash)?)?)
     }
 
     // endregion
 
     // region: add to fs
 
-    pub fn fs_create_ref(&self, branch: &Branch, hash: Hash) -> io::Result<()> {
-        let mut file = OpenOptions::new().create_new(true).write(true).open(self.path.aref(branch))?;
-        file.write(hash_to_hex(hash).as_bytes())?;
-        file.write(b"\n")?;
-        file.flush()?;
+    pub fn fs_create_ref(&self, branch: &Branch, entry: &RefLogEntry) -> anyhow::Result<()> {
+        check_ref_author(&entry.author)?;
+        // a crash must not leave an empty ref behind, which could neither be read nor created again
+        write_file_new(self.path.tmp_file(), &self.path.aref(branch), format_ref_line(entry).as_bytes())?;
         Ok(())
     }
 
-    pub fn fs_update_ref(&self, branch: &Branch, hash: Hash) -> io::Result<()> {
+    pub fn fs_update_ref(&self, branch: &Branch, entry: &RefLogEntry) -> anyhow::Result<()> {
+        check_ref_author(&entry.author)?;
         let mut file = OpenOptions::new().create(true).append(true).open(self.path.aref(branch))?;
-        file.write(hash_to_hex(hash).as_bytes())?;
-        file.write(b"\n")?;
-        file.flush()?;
+        file.write_all(format_ref_line(entry).as_bytes())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Appends `entry` only if the branch still points at `expected`, under the ref lock.
+    pub fn fs_update_ref_cas(&self, branch: &Branch, expected: Hash, entry: &RefLogEntry) -> anyhow::Result<()> {
+        let _lock = RefLock::acquire(self.path.ref_lock(branch))?;
+        self.check_ref(branch, expected)?;
+        self.fs_update_ref(branch, entry)?;
+        Ok(())
+    }
+
+    pub fn fs_delete_ref(&self, branch: &Branch) -> anyhow::Result<()> {
+        let _lock = RefLock::acquire(self.path.ref_lock(branch))?;
+        fs::remove_file(self.path.aref(branch))?;
+        Ok(())
+    }
+
+    pub fn fs_archive_ref(&self, branch: &Branch) -> anyhow::Result<()> {
+        let _lock = RefLock::acquire(self.path.ref_lock(branch))?;
+        let dest = self.path.archived_ref(branch);
+        if file_detected(&dest)? {
+            return Err(anyhow::anyhow!("archived ref {} already exists", branch.to_string()));
+        }
+        fs::rename(self.path.aref(branch), &dest)?;
+        sync_parent_dir(&dest)?;
+        Ok(())
+    }
+
+    /// Deletes blobs, loose or packed, or moves them to `quarantine/<kind>/` to be restored by hand.
+    pub fn fs_remove_blobs(&self, blobs: &[(BlobKind, Hash)], quarantine: bool) -> anyhow::Result<()> {
+        if quarantine {
+            // all before removing any, a page may be the delta base of another
+            for &(kind, hash) in blobs {
+                let dest = self.path.quarantined(kind, hash);
+                fs::create_dir_all(dest.parent().unwrap())?;
+                write_file_atomic(self.path.tmp_file(), &dest, &self.get_blob(kind, hash)?)?;
+            }
+        }
+        let mut packed = HashSet::new();
+        for &(kind, hash) in blobs {
+            let path = self.path.blob(kind, hash);
+            if file_detected(&path)? {
+                fs::remove_file(&path)?;
+                sync_parent_dir(&path)?;
+            }
+            if self.find_packed(kind, hash)?.is_some() {
+                packed.insert((kind, hash));
+            }
+        }
+        if !packed.is_empty() {
+            self.rewrite_packs(false, &packed)?;
+        }
         Ok(())
     }
 
@@ -219,68 +771,345 @@ impl Repo {
 
     // region: add all
 
+    /// `blob` is canonical or a page delta, it is compressed here as configured.
+    fn write_stored_blob(&self, kind: BlobKind, hash: Hash, blob: &[u8]) -> io::Result<()> {
+        write_blob(self.path.tmp_file(), self.path.blob(kind, hash), &compress_blob(self.config.compression, blob)?)
+    }
+
+    /// Returns whether the blob is new to the repo, a packed one is still written loose to freshen it.
+    fn write_object(&self, kind: BlobKind, hash: Hash, blob: &[u8]) -> io::Result<bool> {
+        let packed = self.packs.read().unwrap().packs.iter().any(|pack| pack.contains(kind, hash));
+        Ok(write_object(self.path.tmp_file(), self.path.blob(kind, hash), &compress_blob(self.config.compression, blob)?)? && !packed)
+    }
+
     pub fn add_data_object(&self, content: Json) -> anyhow::Result<Hash> {
         // TODO content: HashMap<String, Json>
         // TODO schema check
         let blob = msgpack_encode(json_to_msgpack(content.clone()))?;
         let hash = hash_all(&blob);
-        write_blob(self.path.data_object(hash), &blob)?;
-        self.db_tx.send(DbOp::AddDataObject { hash, content })?;
+        // same content may be submitted again, e.g. as a merge resolution
+        if self.write_object(BlobKind::Data, hash, &blob)? {
+            self.db_tx.send(DbOp::AddDataObject { hash, content })?;
+        }
         Ok(hash)
     }
 
     pub fn add_page_object(&self, content: String) -> anyhow::Result<Hash> {
-        let blob = content.as_bytes();
-        let hash = hash_all(blob);
-        write_blob(self.path.page_object(hash), blob)?;
-        self.db_tx.send(DbOp::AddPageObject { hash, content })?;
+        self.add_page_object_against(content, None)
+    }
+
+    /// Like `add_page_object`, but the page may be stored as a delta against `base`, see `RepoConfig.page_delta_depth`.
+    pub fn add_page_object_against(&self, content: String, base: Option<Hash>) -> anyhow::Result<Hash> {
+        let hash = hash_all(content.as_bytes());
+        let delta = match base {
+            Some(base) if base != hash => self.encode_page_delta(&content, base)?,
+            _ => None,
+        };
+        if self.write_object(BlobKind::Page, hash, delta.as_deref().unwrap_or(content.as_bytes()))? {
+            self.db_tx.send(DbOp::AddPageObject { hash, content })?;
+        }
         Ok(hash)
     }
 
+    fn encode_page_delta(&self, content: &str, base: Hash) -> anyhow::Result<Option<Vec<u8>>> {
+        let limit = self.config.page_delta_depth;
+        if limit == 0 || !self.has_blob(BlobKind::Page, base)? {
+            return Ok(None);
+        }
+        // a page already stored, maybe in a pack and so written again, may be the base of `base` itself
+        if self.has_blob(BlobKind::Page, hash_all(content.as_bytes()))? {
+            return Ok(None);
+        }
+        let depth = self.get_page_delta(base)?.map_or(0, |delta| delta.depth) + 1;
+        if depth > limit {
+            return Ok(None);
+        }
+        let delta = PageDelta::new(base, depth, &self.get_page_object(base)?, content).encode()?;
+        // rewritten or short pages are smaller in full
+        Ok(if delta.len() < content.len() { Some(delta) } else { None })
+    }
+
     pub fn add_commit(&self, commit: &Commit) -> anyhow::Result<Hash> {
         let blob = rmp_serde::to_vec_named(commit)?;
         let hash = hash_all(&blob);
-        write_blob(self.path.commit(hash), &blob)?;
-        self.db_tx.send(DbOp::AddCommit { hash, commit: commit.clone() })?;
+        // the same commit may be made again, e.g. when replayed by two rebases onto the same tip
+        if self.write_object(BlobKind::Commit, hash, &blob)? {
+            self.db_tx.send(DbOp::AddCommit { hash, commit: commit.clone() })?;
+        }
         Ok(hash)
     }
 
+    /// A state is derived from its commit, so an existing one is the same.
     pub fn add_state(&self, hash: Hash, state: State) -> anyhow::Result<()> {
         let blob = rmp_serde::to_vec_named(&state)?;
-        write_blob(self.path.state(hash), &blob)?;
-        self.db_tx.send(DbOp::AddState { hash, state })?;
+        if self.write_object(BlobKind::State, hash, &blob)? {
+            self.db_tx.send(DbOp::AddState { hash, state })?;
+        }
+        Ok(())
+    }
+
+    /// Tags are never moved, creating an existing one fails.
+    pub fn create_tag(&self, name: &str, tag: Tag) -> anyhow::Result<()> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(anyhow::anyhow!("invalid tag name {:?}", name));
+        }
+        // only existing commits can be tagged
+        let _ = self.get_commit(tag.hash)?;
+        match write_blob(self.path.tmp_file(), self.path.tag(name), &rmp_serde::to_vec_named(&tag)?) {
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Err(anyhow::anyhow!("tag {} already exists", name)),
+            result => result?,
+        }
+        self.db_tx.send(DbOp::CreateTag { name: name.to_owned(), tag })?;
+        Ok(())
+    }
+
+    pub fn create_ref(&self, branch: &Branch, entry: RefLogEntry) -> anyhow::Result<()> {
+        self.fs_create_ref(branch, &entry)?;
+        self.db_tx.send(DbOp::CreateRef { branch: branch.clone(), entry })?;
+        Ok(())
+    }
+
+    pub fn update_ref(&self, branch: &Branch, expected: Hash, entry: RefLogEntry) -> anyhow::Result<()> {
+        self.fs_update_ref_cas(branch, expected, &entry)?;
+        self.db_tx.send(DbOp::UpdateRef { branch: branch.clone(), entry })?;
         Ok(())
     }
 
-    pub fn create_ref(&self, branch: &Branch, hash: Hash) -> anyhow::Result<()> {
-        self.fs_create_ref(branch, hash)?;
-        self.db_tx.send(DbOp::CreateRef { branch: branch.clone(), hash })?;
+    pub fn delete_ref(&self, branch: &Branch) -> anyhow::Result<()> {
+        self.fs_delete_ref(branch)?;
+        self.db_tx.send(DbOp::DeleteRef { branch: branch.clone() })?;
         Ok(())
     }
 
-    pub fn update_ref(&self, branch: &Branch, hash: Hash) -> anyhow::Result<()> {
-        self.fs_update_ref(branch, hash)?;
-        self.db_tx.send(DbOp::UpdateRef { branch: branch.clone(), hash })?;
+    pub fn archive_ref(&self, branch: &Branch) -> anyhow::Result<()> {
+        self.fs_archive_ref(branch)?;
+        self.db_tx.send(DbOp::ArchiveRef { branch: branch.clone() })?;
         Ok(())
     }
 
     // endregion
 
-    // region: high level methods
+    // region: packs
 
-    pub fn commit(&self, commit: Commit, branches: Vec<&Branch>) -> anyhow::Result<()> {
-        let hash = self.add_commit(&commit)?;
+    /// Re-reads the pack indexes, returns the packs now loaded.
+    pub fn load_packs(&self) -> io::Result<Vec<Arc<Pack>>> {
+        let modified = fs::metadata(self.path.packs())?.modified()?;
+        let dir_modified = match modified.elapsed() {
+            Ok(age) if age >= PACKS_MTIME_RESOLUTION => Some(modified),
+            _ => None,
+        };
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(self.path.packs())? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == INDEX_EXT) {
+                paths.push(path.with_extension(PACK_EXT));
+            }
+        }
+        paths.sort();
+        let mut packs = Vec::new();
+        for path in paths {
+            packs.push(Arc::new(Pack::open(path)?));
+        }
+        *self.packs.write().unwrap() = LoadedPacks { packs: packs.clone(), dir_modified };
+        Ok(packs)
+    }
+
+    fn find_packed(&self, kind: BlobKind, hash: Hash) -> io::Result<Option<Arc<Pack>>> {
+        let found = |packs: &[Arc<Pack>]| packs.iter().find(|pack| pack.contains(kind, hash)).cloned();
+        {
+            let loaded = self.packs.read().unwrap();
+            match found(&loaded.packs) {
+                Some(pack) => if file_detected(pack.path())? {
+                    return Ok(Some(pack));
+                },
+                // misses are common, only a changed `packs/` is worth re-reading the indexes
+                None => if loaded.dir_modified.is_some() && loaded.dir_modified == Some(fs::metadata(self.path.packs())?.modified()?) {
+                    return Ok(None);
+                },
+            }
+        }
+        // the blob may have been packed, or its pack replaced, by another process
+        Ok(found(&self.load_packs()?))
+    }
+
+    fn read_packed(&self, kind: BlobKind, hash: Hash) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            let pack = match self.find_packed(kind, hash)? {
+                Some(pack) => pack,
+                None => return Ok(None),
+            };
+            match pack.read(kind, hash) {
+                // replaced by a concurrent repack after it was found, look again
+                Err(err) if is_file_not_found(&err) => continue,
+                result => return result,
+            }
+        }
+    }
 
-        for branch in branches {
-            self.update_ref(branch, hash)?;
+    /// Moves all loose blobs into a new pack, which also takes over the content of the existing packs.
+    /// Returns the number of blobs in the new pack.
+    pub fn repack(&self) -> anyhow::Result<usize> {
+        self.rewrite_packs(true, &HashSet::new())
+    }
+
+    /// Replaces all packs by one without `exclude`, with the loose blobs added if `include_loose`.
+    fn rewrite_packs(&self, include_loose: bool, exclude: &HashSet<(BlobKind, Hash)>) -> anyhow::Result<usize> {
+        let _lock = RefLock::acquire(self.path.pack_lock())?;
+        let old = self.load_packs()?;
+        // written to all along, so `recover` never takes it for a leftover however long the repack
+        let tmp = self.path.tmp_file();
+        let mut writer = PackWriter::create(&tmp)?;
+        for pack in &old {
+            for (kind, hash) in pack.entries() {
+                if !exclude.contains(&(kind, hash)) {
+                    writer.add(kind, hash, &pack.read(kind, hash)?.unwrap())?;
+                }
+            }
         }
+        let mut loose = Vec::new();
+        if include_loose {
+            for kind in BlobKind::ALL {
+                for hash in self.list_loose_blobs(kind)? {
+                    if !exclude.contains(&(kind, hash)) {
+                        writer.add(kind, hash, &read_blob(self.path.blob(kind, hash))?)?;
+                        loose.push((kind, hash));
+                    }
+                }
+            }
+        }
+        let count = writer.len();
+        let index = writer.finish()?;
+        let new = if count == 0 {
+            fs::remove_file(&tmp)?;
+            None
+        } else {
+            // named by content, so rewriting unchanged packs replaces them by themselves
+            let path = self.path.pack(&hash_to_hex(hash_all(&index)));
+            fs::rename(&tmp, &path)?;
+            // the index goes last, a pack without one is never read
+            write_file_atomic(self.path.tmp_file(), &path.with_extension(INDEX_EXT), &index)?;
+            Some(path)
+        };
+        for pack in &old {
+            if Some(pack.path()) != new.as_deref() {
+                fs::remove_file(pack.path().with_extension(INDEX_EXT))?;
+                fs::remove_file(pack.path())?;
+            }
+        }
+        for (kind, hash) in loose {
+            fs::remove_file(self.path.blob(kind, hash))?;
+        }
+        sync_parent_dir(&self.path.pack("new"))?;
+        self.load_packs()?;
+        Ok(count)
+    }
 
-        let mut state = self.get_state(commit.prev)?;
-        state.update(commit.rev);
+    // endregion
+
+    // region: high level methods
+
+    /// `branches` are moved to the new commit only if still at the paired hash.
+    pub fn commit(&self, commit: Commit, branches: Vec<(&Branch, Hash)>, op: RefOp) -> anyhow::Result<Hash> {
+        let Commit { prev, ts, ref author, .. } = commit;
+        let author = author.clone();
+        let mut state = self.get_state(prev)?;
+        state.update(commit.rev.clone());
+
+        // refs are moved last: after a crash before that, the commit and state are
+        // unreachable leftovers and no ref points at a commit missing its state
+        let hash = self.add_commit(&commit)?;
         self.add_state(hash, state)?;
 
-        Ok(())
+        for (branch, expected) in branches {
+            self.update_ref(branch, expected, RefLogEntry { hash, ts, author: author.clone(), op })?;
+        }
+
+        Ok(hash)
     }
 
     // endregion
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+    use crate::{prelude::*, model::*, command::*, testutil::TestRepo};
+    use super::{Repo, RefLock};
+
+    fn backdate(path: &Path) {
+        let old = SystemTime::now() - Duration::from_secs(10 * 60);
+        OpenOptions::new().write(true).open(path).unwrap().set_modified(old).unwrap();
+    }
+
+    #[test]
+    fn names_like_temp_files_survive_recover() {
+        let repo = TestRepo::new("tmp-names", RepoConfig::default());
+        let tip = repo.commit_pages(&Main, 1, &[("a", "a\n")]);
+        let commit = hash_to_hex(tip).to_string();
+        repo.exec(2, CommandInner::CreateTag(CCreateTag { name: "v1.tmp".to_owned(), commit: commit.clone(), message: String::new() })).unwrap();
+        // common branches are named after their author
+        Repo::exec(&repo, Command { ts: 3, author: "someone.tmp".to_owned(), inner: CommandInner::CreateCommonBranch(CCreateCommonBranch { prev: commit }) }).unwrap();
+        let branch = Branch::Common(CommonBranch { ts: 3, author: "someone.tmp".to_owned() });
+        backdate(&repo.dir.join("tags").join("v1.tmp"));
+        backdate(&repo.dir.join("refs").join(branch.to_string()));
+        let (reopened, _db_rx) = repo.reopen();
+        assert_eq!(reopened.get_tag("v1.tmp").unwrap().hash, tip);
+        assert_eq!(reopened.get_ref(&branch).unwrap(), tip);
+        assert!(reopened.list_branches().unwrap().iter().any(|info| info.branch == branch));
+    }
+
+    #[test]
+    fn tags_listed_with_a_tag_write_in_flight() {
+        let repo = TestRepo::new("tag-in-flight", RepoConfig::default());
+        let tip = repo.commit_pages(&Main, 1, &[("a", "a\n")]);
+        let commit = hash_to_hex(tip).to_string();
+        repo.exec(2, CommandInner::CreateTag(CCreateTag { name: "v1".to_owned(), commit, message: String::new() })).unwrap();
+        // what a crash or a concurrent `create_tag` leaves before the tag is linked in place
+        fs::write(repo.dir.join("tmp").join("1-0"), &b"\x84\xa4hash"[..]).unwrap();
+        let tags = repo.list_tags().unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].0, "v1");
+        assert!(repo.gc(&crate::gc::GcOptions { dry_run: true, ..Default::default() }).is_ok());
+    }
+
+    #[test]
+    fn ref_lock_of_a_dead_writer_is_not_held() {
+        let repo = TestRepo::new("ref-lock", RepoConfig::default());
+        let tip = repo.commit_pages(&Main, 1, &[("a", "a\n")]);
+        // left by a writer that crashed, long-running writers never reopen the repo
+        fs::write(repo.dir.join("locks").join("main"), b"").unwrap();
+        backdate(&repo.dir.join("locks").join("main"));
+        let next = repo.commit_pages(&Main, 2, &[("a", "b\n")]);
+        assert_eq!(repo.get_commit(next).unwrap().prev, tip);
+        let lock = RefLock::acquire(repo.dir.join("locks").join("main")).unwrap();
+        let entry = RefLogEntry { hash: tip, ts: 3, author: String::new(), op: RefOp::Reset };
+        assert!(repo.fs_update_ref_cas(&Main, next, &entry).is_err());
+        drop(lock);
+        repo.fs_update_ref_cas(&Main, next, &entry).unwrap();
+        assert_eq!(repo.get_ref(&Main).unwrap(), tip);
+    }
+
+    #[test]
+    fn pack_lock_survives_recover() {
+        let repo = TestRepo::new("pack-lock", RepoConfig::default());
+        let path = repo.dir.join("packs").join("lock");
+        // a repack of many blobs outlasts the age leftovers are removed at
+        let _lock = RefLock::acquire(path.clone()).unwrap();
+        backdate(&path);
+        let _reopened = repo.reopen();
+        assert!(RefLock::acquire(path).is_err());
+    }
+
+    #[test]
+    fn recover_removes_leftover_temp_files() {
+        let repo = TestRepo::new("tmp-leftover", RepoConfig::default());
+        let (leftover, recent) = (repo.dir.join("tmp").join("1-0"), repo.dir.join("tmp").join("1-1"));
+        fs::write(&leftover, b"partial").unwrap();
+        fs::write(&recent, b"partial").unwrap();
+        backdate(&leftover);
+        let _reopened = repo.reopen();
+        assert!(!leftover.exists());
+        // may still be written by a live writer
+        assert!(recent.exists());
+    }
+}
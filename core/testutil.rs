@@ -0,0 +1,72 @@
+use std::ops::Deref;
+use crate::{prelude::*, model::*, command::*, repo::{Repo, DbOp}};
+
+/// A repo in a fresh temp dir, removed on drop.
+pub struct TestRepo {
+    pub dir: PathBuf,
+    repo: Repo,
+    // commands fail once nothing receives the db ops
+    _db_rx: Receiver<DbOp>,
+}
+
+impl TestRepo {
+    pub fn new(name: &str, config: RepoConfig) -> TestRepo {
+        let dir = std::env::temp_dir().join(format!("lesserbase-{}-{}-{}", name, std::process::id(), now()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config"), toml::to_vec(&config).unwrap()).unwrap();
+        let (repo, db_rx) = Repo::new(dir.clone()).unwrap();
+        TestRepo { dir, repo, _db_rx: db_rx }
+    }
+
+    /// Opens the repo again, as another process would.
+    pub fn reopen(&self) -> (Repo, Receiver<DbOp>) {
+        Repo::new(self.dir.clone()).unwrap()
+    }
+
+    pub fn exec(&self, ts: u64, inner: CommandInner) -> anyhow::Result<()> {
+        self.repo.exec(Command { ts, author: "tester".to_owned(), inner })
+    }
+
+    /// Commits `pages` onto the tip of `branch`, returns the new tip.
+    pub fn commit_pages(&self, branch: &Branch, ts: u64, pages: &[(&str, &str)]) -> Hash {
+        let rev = pages.iter().map(|(path, content)| CRev {
+            inner: CRevInner::Update { content: Json::String(content.to_string()) },
+            object_kind: ObjectKind::Page,
+            path: path.to_string(),
+        }).collect();
+        let prev = hash_to_hex(self.get_ref(branch).unwrap()).to_string();
+        let comment = format!("commit at {}", ts);
+        self.exec(ts, CommandInner::Commit(CCommit { comment, branch: branch.clone(), prev, rev })).unwrap();
+        self.get_ref(branch).unwrap()
+    }
+
+    /// Creates a common branch at `at`, `ts` tells branches apart.
+    pub fn branch_at(&self, ts: u64, at: Hash) -> Branch {
+        self.exec(ts, CommandInner::CreateCommonBranch(CCreateCommonBranch { prev: hash_to_hex(at).to_string() })).unwrap();
+        Branch::Common(CommonBranch { ts, author: "tester".to_owned() })
+    }
+
+    pub fn merge(&self, ts: u64, from: &Branch, to: &Branch) -> anyhow::Result<()> {
+        let (from, to) = (from.clone(), to.clone());
+        self.exec(ts, CommandInner::MergeBranch(CMergeBranch { from, to, comment: format!("merge at {}", ts), squash: false }))
+    }
+
+    pub fn page_at(&self, branch: &Branch, path: &str) -> Option<String> {
+        let state = self.get_state(self.get_ref(branch).unwrap()).unwrap();
+        state.page.get(path).map(|hash| self.get_page_object(*hash).unwrap())
+    }
+}
+
+impl Deref for TestRepo {
+    type Target = Repo;
+
+    fn deref(&self) -> &Repo {
+        &self.repo
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
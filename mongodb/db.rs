@@ -80,6 +80,8 @@ pub struct Db {
     coll_vcs_objects_page: ContentCollection,
     coll_vcs_commits: ContentCollection,
     coll_vcs_refs: LooseTypedCollection,
+    coll_vcs_archived_refs: LooseTypedCollection,
+    coll_vcs_tags: LooseTypedCollection,
     coll_vcs_states: LooseTypedCollection,
     coll_latest_data: LooseTypedCollection,
     coll_latest_page: LooseTypedCollection,
@@ -157,6 +159,8 @@ impl Db {
             coll_vcs_objects_page: db_vcs.collection("objects-page"),
             coll_vcs_commits: db_vcs.collection("commits"),
             coll_vcs_refs: db_vcs.collection("refs"),
+            coll_vcs_archived_refs: db_vcs.collection("archived-refs"),
+            coll_vcs_tags: db_vcs.collection("tags"),
             coll_vcs_states: db_vcs.collection("states"),
             coll_latest_data: db_latest.collection("data"),
             coll_latest_page: db_latest.collection("page"),
@@ -166,19 +170,23 @@ impl Db {
 
     pub async fn init(&self) -> anyhow::Result<()> {
         if !id_exists(&self.coll_vcs_refs, Branch::Main.to_string().into()).await? {
-            self.create_ref(&Branch::Main, EMPTY_HASH).await?;
+            let entry = RefLogEntry { hash: EMPTY_HASH, ts: now(), author: String::new(), op: RefOp::Create };
+            self.create_ref(&Branch::Main, &entry).await?;
             self.add_state(EMPTY_HASH, State::empty()).await?;
         }
         Ok(())
     }
 
-    pub async fn create_ref(&self, branch: &Branch, hash: Hash) -> anyhow::Result<()> {
+    pub async fn create_ref(&self, branch: &Branch, entry: &RefLogEntry) -> anyhow::Result<()> {
         let coll = &self.coll_vcs_refs;
         let branch = branch.to_string();
         let doc = bson_doc! {
             "_id": &branch,
             "hashes": [
-                hash_to_bson_bin(hash),
+                hash_to_bson_bin(entry.hash),
+            ],
+            "log": [
+                bson::to_bson(entry)?,
             ],
         };
         let _ = coll.insert_one(doc, None).await?;
@@ -186,11 +194,12 @@ impl Db {
         Ok(())
     }
 
-    pub async fn update_ref(&self, branch: &Branch, hash: Hash) -> anyhow::Result<()> {
+    pub async fn update_ref(&self, branch: &Branch, entry: &RefLogEntry) -> anyhow::Result<()> {
         let coll = &self.coll_vcs_refs;
         let update = bson_doc! {
             "$push": {
-                "hashes": hash_to_bson_bin(hash),
+                "hashes": hash_to_bson_bin(entry.hash),
+                "log": bson::to_bson(entry)?,
             },
         };
         let result = coll.update_one(branch_query(branch), update, None).await?;
@@ -199,6 +208,30 @@ impl Db {
         Ok(())
     }
 
+    pub async fn delete_ref(&self, branch: &Branch) -> anyhow::Result<()> {
+        let coll = &self.coll_vcs_refs;
+        let result = coll.delete_one(branch_query(branch), None).await?;
+        assert_eq!(1, result.deleted_count);
+        Ok(())
+    }
+
+    pub async fn archive_ref(&self, branch: &Branch) -> anyhow::Result<()> {
+        let doc = self.coll_vcs_refs.find_one(branch_query(branch), None).await?.expect("not found");
+        let _ = self.coll_vcs_archived_refs.insert_one(doc, None).await?;
+        // [opt assert] id == result.inserted_id
+        self.delete_ref(branch).await
+    }
+
+    pub async fn get_reflog(&self, branch: &Branch) -> anyhow::Result<Vec<RefLogEntry>> {
+        let coll = &self.coll_vcs_refs;
+        let result = coll.find_one(branch_query(branch), None).await?.expect("not found");
+        let mut reflog = Vec::new();
+        for entry in result.get_array("log")? {
+            reflog.push(bson::from_bson(entry.clone())?);
+        }
+        Ok(reflog)
+    }
+
     pub async fn get_ref(&self, branch: &Branch) -> anyhow::Result<Hash> {
         let coll = &self.coll_vcs_refs;
         let result = coll.find_one(branch_query(branch), None).await?.expect("not found");
@@ -206,6 +239,20 @@ impl Db {
         Ok(bson_to_hash(result.get_array("hashes")?.last().unwrap().clone())?)
     }
 
+    pub async fn create_tag(&self, name: &str, tag: &Tag) -> anyhow::Result<()> {
+        let coll = &self.coll_vcs_tags;
+        let doc = with_path_id(bson::to_document(tag)?, name);
+        let _ = coll.insert_one(doc, None).await?;
+        // [opt assert] id == result.inserted_id
+        Ok(())
+    }
+
+    pub async fn get_tag(&self, name: &str) -> anyhow::Result<Tag> {
+        let coll = &self.coll_vcs_tags;
+        let result = coll.find_one(path_query(name), None).await?.expect("not found");
+        Ok(bson::from_document(without_id(result))?)
+    }
+
     #[inline]
     pub async fn add_page_object(&self, hash: Hash, content: String) -> anyhow::Result<()> {
         write_content(&self.coll_vcs_objects_page, hash, content.into()).await